@@ -0,0 +1,53 @@
+//! Comparators that define the key order of the ordered collections in this crate.
+
+use std::cmp::Ordering;
+
+/// Defines a total order on `T`.
+///
+/// Implemented by [`Natural`], [`TotalOrder`], [`Reverse`] and any
+/// `Fn(&T, &T) -> Ordering`, so for example a case insensitive order on strings is just a closure.
+pub trait Comparator<T: ?Sized> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+/// Orders keys by their [`Ord`] implementation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Natural;
+
+impl<T: Ord + ?Sized> Comparator<T> for Natural {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+/// Orders floats by their `total_cmp`, which places `NaN`s after all other values.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TotalOrder;
+
+impl Comparator<f32> for TotalOrder {
+    fn compare(&self, a: &f32, b: &f32) -> Ordering {
+        a.total_cmp(b)
+    }
+}
+
+impl Comparator<f64> for TotalOrder {
+    fn compare(&self, a: &f64, b: &f64) -> Ordering {
+        a.total_cmp(b)
+    }
+}
+
+/// Inverts the order of the wrapped comparator.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Reverse<C>(pub C);
+
+impl<T: ?Sized, C: Comparator<T>> Comparator<T> for Reverse<C> {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self.0.compare(b, a)
+    }
+}
+
+impl<T: ?Sized, F: Fn(&T, &T) -> Ordering> Comparator<T> for F {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
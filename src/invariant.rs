@@ -0,0 +1,53 @@
+//! Errors reported by the structural invariant checks of the tree based collections.
+
+use std::{error::Error, fmt};
+
+/// The first structural invariant found broken while walking a tree, naming the key of the
+/// offending node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantError<'a, K> {
+    /// The key is not between the keys of its ancestors.
+    KeyOrder { key: &'a K },
+    /// The node has a higher priority than its parent.
+    HeapOrder { key: &'a K },
+    /// The `parent` pointer of the node does not point to the node it hangs below.
+    ParentLink { key: &'a K },
+    /// The cached subtree size of the node does not match its children.
+    Size {
+        key: &'a K,
+        expected: usize,
+        found: usize,
+    },
+    /// The cached length of the collection does not match its number of nodes.
+    Len { expected: usize, found: usize },
+}
+
+impl<'a, K: fmt::Debug> fmt::Display for InvariantError<'a, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KeyOrder { key } => write!(f, "key {:?} is out of order", key),
+            Self::HeapOrder { key } => {
+                write!(f, "node {:?} has a higher priority than its parent", key)
+            }
+            Self::ParentLink { key } => write!(f, "node {:?} has a wrong parent pointer", key),
+            Self::Size {
+                key,
+                expected,
+                found,
+            } => write!(
+                f,
+                "node {:?} has size {} but its subtree holds {} nodes",
+                key, found, expected
+            ),
+            Self::Len { expected, found } => {
+                write!(
+                    f,
+                    "length is {} but the tree holds {} nodes",
+                    found, expected
+                )
+            }
+        }
+    }
+}
+
+impl<'a, K: fmt::Debug> Error for InvariantError<'a, K> {}
@@ -1,3 +1,6 @@
+pub mod compare;
+pub mod invariant;
+pub mod monoid;
 #[cfg(feature = "rand")]
 pub mod rand;
 
@@ -0,0 +1,107 @@
+//! Monoids that summarize the values of the augmented collections in this crate.
+
+use std::ops::Add;
+
+/// An associative `combine` on summaries of values of type `V`, with `identity` as its neutral
+/// element.
+///
+/// A value enters the monoid through `lift`, so the summary can be a different type than the
+/// value, like the `Option<V>` that [`Min`] and [`Max`] use for the empty summary.
+pub trait Monoid<V: ?Sized> {
+    type Summary: Clone;
+
+    fn identity(&self) -> Self::Summary;
+    fn lift(&self, value: &V) -> Self::Summary;
+    fn combine(&self, a: &Self::Summary, b: &Self::Summary) -> Self::Summary;
+}
+
+/// Adds up values, starting from their `Default`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sum;
+
+impl<V: Clone + Default + Add<Output = V>> Monoid<V> for Sum {
+    type Summary = V;
+
+    fn identity(&self) -> V {
+        V::default()
+    }
+
+    fn lift(&self, value: &V) -> V {
+        value.clone()
+    }
+
+    fn combine(&self, a: &V, b: &V) -> V {
+        a.clone() + b.clone()
+    }
+}
+
+/// Keeps the smallest value, `None` for no values at all.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Min;
+
+impl<V: Clone + Ord> Monoid<V> for Min {
+    type Summary = Option<V>;
+
+    fn identity(&self) -> Option<V> {
+        None
+    }
+
+    fn lift(&self, value: &V) -> Option<V> {
+        Some(value.clone())
+    }
+
+    fn combine(&self, a: &Option<V>, b: &Option<V>) -> Option<V> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b).clone()),
+            _ => a.as_ref().or(b.as_ref()).cloned(),
+        }
+    }
+}
+
+/// Keeps the largest value, `None` for no values at all.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Max;
+
+impl<V: Clone + Ord> Monoid<V> for Max {
+    type Summary = Option<V>;
+
+    fn identity(&self) -> Option<V> {
+        None
+    }
+
+    fn lift(&self, value: &V) -> Option<V> {
+        Some(value.clone())
+    }
+
+    fn combine(&self, a: &Option<V>, b: &Option<V>) -> Option<V> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.max(b).clone()),
+            _ => a.as_ref().or(b.as_ref()).cloned(),
+        }
+    }
+}
+
+/// Adds up the weights that `F` assigns to the values, so that entries carrying more than a
+/// weight can still be sampled by it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Weight<F>(pub F);
+
+impl<V, W, F> Monoid<V> for Weight<F>
+where
+    W: Clone + Default + Add<Output = W>,
+    F: Fn(&V) -> W,
+{
+    type Summary = W;
+
+    fn identity(&self) -> W {
+        W::default()
+    }
+
+    fn lift(&self, value: &V) -> W {
+        (self.0)(value)
+    }
+
+    fn combine(&self, a: &W, b: &W) -> W {
+        a.clone() + b.clone()
+    }
+}
@@ -1,7 +1,6 @@
 use rand::{rngs::ThreadRng, thread_rng, Rng};
 use std::{
     cell::Cell,
-    f64::EPSILON,
     fmt::Debug,
     mem::{self, MaybeUninit},
 };
@@ -52,20 +51,29 @@ impl<const S: usize> AliasBuilder<S> {
         let mut containers = [MaybeUninit::<Container>::uninit(); S];
         while let Some((thresh, pos)) = self.small.pop() {
             let rest = 1.0 - thresh;
-            if rest > EPSILON {
-                let (mut p, i) = self.big.pop().expect("large counterpart");
-                containers[pos] = MaybeUninit::new(Container { value: i, thresh });
-                p -= rest;
-                if p > 1.0 {
-                    self.big.push(p, i);
-                } else {
-                    self.small.push(p, i);
-                }
+            // Rounding errors can leave a small bucket without a large counterpart, in which case
+            // it keeps the whole bucket for itself.
+            let counterpart = if rest > f64::EPSILON {
+                self.big.pop()
             } else {
-                containers[pos] = MaybeUninit::new(Container {
-                    value: pos,
-                    thresh: 1.0,
-                });
+                None
+            };
+            match counterpart {
+                Some((mut p, i)) => {
+                    containers[pos] = MaybeUninit::new(Container { value: i, thresh });
+                    p -= rest;
+                    if p > 1.0 {
+                        self.big.push(p, i);
+                    } else {
+                        self.small.push(p, i);
+                    }
+                }
+                None => {
+                    containers[pos] = MaybeUninit::new(Container {
+                        value: pos,
+                        thresh: 1.0,
+                    });
+                }
             }
         }
         while let Some((_, value)) = self.big.pop() {
@@ -77,7 +85,13 @@ impl<const S: usize> AliasBuilder<S> {
 
 impl<const S: usize> Alias<S> {
     pub fn new(dist: &[f64; S]) -> Self {
-        assert!(1.0 - dist.iter().sum::<f64>() < EPSILON);
+        assert!(dist.iter().sum::<f64>() > 1.0 - f64::EPSILON);
+        Self::new_unchecked(dist)
+    }
+
+    /// Builds the table without checking that `dist` sums up to one, for distributions that
+    /// are only normalized up to rounding errors.
+    pub(super) fn new_unchecked(dist: &[f64; S]) -> Self {
         let builder = AliasBuilder::new(dist);
         println!("{:?}", builder);
 
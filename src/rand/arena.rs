@@ -0,0 +1,1176 @@
+use super::rtree::check_range;
+use crate::{
+    compare::{Comparator, Natural},
+    invariant::InvariantError,
+};
+use rand::{thread_rng, Rng};
+use std::{
+    borrow::Borrow,
+    cell::UnsafeCell,
+    cmp::Ordering,
+    fmt::Debug,
+    iter::FusedIterator,
+    marker::PhantomData,
+    mem,
+    ops::{Bound, RangeBounds},
+};
+
+/// Index of a node in the arena, `NIL` marks a missing link.
+type Idx = u32;
+const NIL: Idx = Idx::MAX;
+
+/// An ordered map backed by a treap whose nodes live in a single growable arena.
+///
+/// Nodes reference each other through `u32` indices instead of pointers and freed slots are
+/// reused through a free list, so a map that grows and shrinks repeatedly does not hit the
+/// allocator once it reached its peak size. Like [`RTreeMap`](super::RTreeMap) it draws the
+/// priorities from the thread local generator.
+pub struct ArenaTreeMap<K, V, C = Natural> {
+    slots: Vec<Slot<K, V>>,
+    /// Head of the linked list of free slots.
+    free: Idx,
+    root: Idx,
+    len: usize,
+    cmp: C,
+}
+
+enum Slot<K, V> {
+    Occupied(Node<K, V>),
+    Free { next: Idx },
+}
+
+struct Node<K, V> {
+    key: K,
+    /// Only written to through `&mut ArenaTreeMap`. The cell lets [`IterMut`] hand out
+    /// references to the values while it still walks the links of the nodes, which a safe
+    /// iterator could only do by collecting a reference to every slot up front, making
+    /// [`ArenaTreeMap::range_mut`] O(n).
+    value: UnsafeCell<V>,
+    priority: usize,
+    size: u32,
+    parent: Idx,
+    left: Idx,
+    right: Idx,
+}
+
+impl<K, V> Node<K, V> {
+    fn value(&self) -> &V {
+        // SAFETY: Values are only written through `&mut ArenaTreeMap`, and the node is borrowed
+        // from the map, so there is no writer while this reference lives.
+        unsafe { &*self.value.get() }
+    }
+}
+
+impl<K, V> ArenaTreeMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_comparator(Natural)
+    }
+
+    /// Creates an empty map with room for `capacity` entries before it reallocates.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_comparator(capacity, Natural)
+    }
+}
+
+// SAFETY: `UnsafeCell` is what keeps the map from being `Sync` automatically. Through a shared
+// reference the values are only ever read, by `Node::value`. They are written only through
+// `UnsafeCell::get_mut` or an `IterMut`, which both borrow the map mutably, so no thread can
+// write a value while another one reads it. Sharing the map is then as safe as sharing its
+// keys, values and comparator.
+unsafe impl<K: Sync, V: Sync, C: Sync> Sync for ArenaTreeMap<K, V, C> {}
+
+impl<K, V, C> ArenaTreeMap<K, V, C> {
+    /// Creates an empty map that orders its keys by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self {
+        Self::with_capacity_and_comparator(0, cmp)
+    }
+
+    /// Creates an empty map that orders its keys by `cmp`, with room for `capacity` entries
+    /// before it reallocates.
+    pub fn with_capacity_and_comparator(capacity: usize, cmp: C) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            free: NIL,
+            root: NIL,
+            len: 0,
+            cmp,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of entries the map can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        let free = self.slots.len() - self.len;
+        self.slots.reserve(additional.saturating_sub(free));
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.free = NIL;
+        self.root = NIL;
+        self.len = 0;
+    }
+
+    /// Moves all entries to the front of the arena and releases the unused memory.
+    pub fn shrink_to_fit(&mut self) {
+        let holes: Vec<Idx> = (0..self.len as Idx)
+            .filter(|&i| matches!(self.slot(i), Slot::Free { .. }))
+            .collect();
+        let mut holes = holes.into_iter();
+        for i in self.len as Idx..self.slots.len() as Idx {
+            if let Slot::Occupied(_) = self.slot(i) {
+                let hole = holes.next().expect("a hole for every entry past len");
+                self.relocate(i, hole);
+            }
+        }
+        self.slots.truncate(self.len);
+        self.slots.shrink_to_fit();
+        self.free = NIL;
+    }
+
+    /// Moves the node at `from` into the free slot `to`, updating all links pointing to it.
+    fn relocate(&mut self, from: Idx, to: Idx) {
+        self.slots.swap(from as usize, to as usize);
+        let (parent, left, right) = {
+            let n = self.node(to);
+            (n.parent, n.left, n.right)
+        };
+        if parent == NIL {
+            self.root = to;
+        } else if self.node(parent).left == from {
+            self.node_mut(parent).left = to;
+        } else {
+            self.node_mut(parent).right = to;
+        }
+        for child in [left, right] {
+            if child != NIL {
+                self.node_mut(child).parent = to;
+            }
+        }
+    }
+
+    fn slot(&self, i: Idx) -> &Slot<K, V> {
+        &self.slots[i as usize]
+    }
+
+    fn node(&self, i: Idx) -> &Node<K, V> {
+        match &self.slots[i as usize] {
+            Slot::Occupied(n) => n,
+            Slot::Free { .. } => unreachable!("link to a free slot"),
+        }
+    }
+
+    fn node_mut(&mut self, i: Idx) -> &mut Node<K, V> {
+        match &mut self.slots[i as usize] {
+            Slot::Occupied(n) => n,
+            Slot::Free { .. } => unreachable!("link to a free slot"),
+        }
+    }
+
+    fn size_of(&self, i: Idx) -> u32 {
+        if i == NIL {
+            0
+        } else {
+            self.node(i).size
+        }
+    }
+
+    fn update_size(&mut self, i: Idx) {
+        let size = 1 + self.size_of(self.node(i).left) + self.size_of(self.node(i).right);
+        self.node_mut(i).size = size;
+    }
+
+    /// Stores `node` in a free slot, growing the arena if there is none.
+    fn alloc(&mut self, node: Node<K, V>) -> Idx {
+        if self.free != NIL {
+            let i = self.free;
+            let Slot::Free { next } =
+                mem::replace(&mut self.slots[i as usize], Slot::Occupied(node))
+            else {
+                unreachable!("free list points to an occupied slot")
+            };
+            self.free = next;
+            i
+        } else {
+            assert!(self.slots.len() < NIL as usize, "arena is full");
+            self.slots.push(Slot::Occupied(node));
+            (self.slots.len() - 1) as Idx
+        }
+    }
+
+    fn dealloc(&mut self, i: Idx) -> Node<K, V> {
+        let slot = mem::replace(&mut self.slots[i as usize], Slot::Free { next: self.free });
+        self.free = i;
+        match slot {
+            Slot::Occupied(n) => n,
+            Slot::Free { .. } => unreachable!("double free"),
+        }
+    }
+
+    /// Lifts `x` above its parent, keeping the in order attribute of the tree.
+    fn rotate_up(&mut self, x: Idx) {
+        let p = self.node(x).parent;
+        let g = self.node(p).parent;
+        if self.node(p).left == x {
+            let b = self.node(x).right;
+            self.node_mut(p).left = b;
+            if b != NIL {
+                self.node_mut(b).parent = p;
+            }
+            self.node_mut(x).right = p;
+        } else {
+            let b = self.node(x).left;
+            self.node_mut(p).right = b;
+            if b != NIL {
+                self.node_mut(b).parent = p;
+            }
+            self.node_mut(x).left = p;
+        }
+        self.node_mut(p).parent = x;
+        self.node_mut(x).parent = g;
+        if g == NIL {
+            self.root = x;
+        } else if self.node(g).left == p {
+            self.node_mut(g).left = x;
+        } else {
+            self.node_mut(g).right = x;
+        }
+        self.update_size(p);
+        self.update_size(x);
+    }
+
+    /// Looks for the node holding the key for which `cmp` returns `Equal`, or else returns the
+    /// node below which it would have to be inserted and whether it goes to the left.
+    fn search<F: Fn(&K) -> Ordering>(&self, cmp: F) -> Result<Idx, (Idx, bool)> {
+        let mut parent = (NIL, false);
+        let mut i = self.root;
+        while i != NIL {
+            let n = self.node(i);
+            match cmp(&n.key) {
+                Ordering::Equal => return Ok(i),
+                Ordering::Greater => {
+                    parent = (i, true);
+                    i = n.left;
+                }
+                Ordering::Less => {
+                    parent = (i, false);
+                    i = n.right;
+                }
+            }
+        }
+        Err(parent)
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<Idx>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.search(|k| self.cmp.compare(k.borrow(), key)).ok()
+    }
+
+    /// Links a new node for the entry below `parent`, on the left if `left`, and restores the
+    /// heap order.
+    fn insert_at(&mut self, parent: Idx, left: bool, key: K, value: V) -> Idx {
+        let i = self.alloc(Node {
+            key,
+            value: UnsafeCell::new(value),
+            priority: thread_rng().gen(),
+            size: 1,
+            parent,
+            left: NIL,
+            right: NIL,
+        });
+        if parent == NIL {
+            self.root = i;
+        } else if left {
+            self.node_mut(parent).left = i;
+        } else {
+            self.node_mut(parent).right = i;
+        }
+        let mut p = parent;
+        while p != NIL {
+            self.node_mut(p).size += 1;
+            p = self.node(p).parent;
+        }
+        // Restore the heap order on the priorities.
+        while self.node(i).parent != NIL
+            && self.node(self.node(i).parent).priority < self.node(i).priority
+        {
+            self.rotate_up(i);
+        }
+        self.len += 1;
+        i
+    }
+
+    /// Makes `root` the root of the map, updating the length accordingly.
+    fn set_root(&mut self, root: Idx) {
+        if root != NIL {
+            self.node_mut(root).parent = NIL;
+        }
+        self.root = root;
+        self.len = self.size_of(root) as usize;
+    }
+
+    /// Replaces both children of `i` and recomputes its size.
+    fn set_children(&mut self, i: Idx, left: Idx, right: Idx) {
+        for child in [left, right] {
+            if child != NIL {
+                self.node_mut(child).parent = i;
+            }
+        }
+        let n = self.node_mut(i);
+        n.left = left;
+        n.right = right;
+        self.update_size(i);
+    }
+
+    /// Splits the subtree at `i` into the nodes for which `is_left` holds and the nodes after
+    /// them, returning both roots with stale parent links.
+    ///
+    /// `is_left` has to hold for a prefix of the keys.
+    fn split_by<F: Fn(&Self, Idx) -> bool>(&mut self, i: Idx, is_left: &F) -> (Idx, Idx) {
+        if i == NIL {
+            return (NIL, NIL);
+        }
+        if is_left(self, i) {
+            let (l, r) = self.split_by(self.node(i).right, is_left);
+            self.set_children(i, self.node(i).left, l);
+            (i, r)
+        } else {
+            let (l, r) = self.split_by(self.node(i).left, is_left);
+            self.set_children(i, r, self.node(i).right);
+            (l, i)
+        }
+    }
+
+    /// Merges two subtrees where all keys of `a` are smaller than the keys of `b`.
+    fn merge(&mut self, a: Idx, b: Idx) -> Idx {
+        if a == NIL {
+            return b;
+        }
+        if b == NIL {
+            return a;
+        }
+        if self.node(a).priority > self.node(b).priority {
+            let right = self.merge(self.node(a).right, b);
+            self.set_children(a, self.node(a).left, right);
+            a
+        } else {
+            let left = self.merge(a, self.node(b).left);
+            self.set_children(b, left, self.node(b).right);
+            b
+        }
+    }
+
+    /// Moves the subtree at `root` of `other` into the arena of `self` keeping its shape,
+    /// and returns the index of its new root.
+    ///
+    /// Leaves the length of `other` and the link to the subtree in it for the caller to fix.
+    fn move_from(&mut self, other: &mut Self, root: Idx) -> Idx {
+        let mut new_root = NIL;
+        // Every node still to move with its new parent and the side it hangs on.
+        let mut stack = Vec::new();
+        if root != NIL {
+            stack.push((root, NIL, false));
+        }
+        while let Some((i, parent, left)) = stack.pop() {
+            let node = other.dealloc(i);
+            let (l, r) = (node.left, node.right);
+            let j = self.alloc(Node {
+                parent,
+                left: NIL,
+                right: NIL,
+                ..node
+            });
+            if parent == NIL {
+                new_root = j;
+            } else if left {
+                self.node_mut(parent).left = j;
+            } else {
+                self.node_mut(parent).right = j;
+            }
+            if l != NIL {
+                stack.push((l, j, true));
+            }
+            if r != NIL {
+                stack.push((r, j, false));
+            }
+        }
+        new_root
+    }
+
+    /// Splits the map in two at `key`, returning everything after and including `key`.
+    ///
+    /// The returned entries move into a new arena, which takes O(m) time for m of them.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q> + Clone,
+    {
+        let (left, right) = self.split_by(self.root, &|map, i| {
+            map.cmp.compare(map.node(i).key.borrow(), key).is_lt()
+        });
+        self.set_root(left);
+        let mut other =
+            Self::with_capacity_and_comparator(self.size_of(right) as usize, self.cmp.clone());
+        let root = other.move_from(self, right);
+        other.set_root(root);
+        other
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.find(key).is_some()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.find(key).map(|i| self.node(i).value())
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.find(key).map(|i| self.node_mut(i).value.get_mut())
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let i = self.find(key)?;
+        Some(self.remove_at(i).1)
+    }
+
+    /// Removes the node at `i`, returning its entry.
+    fn remove_at(&mut self, i: Idx) -> (K, V) {
+        // Rotate the node down until it is a leaf.
+        loop {
+            let (l, r) = (self.node(i).left, self.node(i).right);
+            let child = match (l, r) {
+                (NIL, NIL) => break,
+                (NIL, r) => r,
+                (l, NIL) => l,
+                (l, r) if self.node(l).priority >= self.node(r).priority => l,
+                (_, r) => r,
+            };
+            self.rotate_up(child);
+        }
+        let parent = self.node(i).parent;
+        if parent == NIL {
+            self.root = NIL;
+        } else if self.node(parent).left == i {
+            self.node_mut(parent).left = NIL;
+        } else {
+            self.node_mut(parent).right = NIL;
+        }
+        let mut p = parent;
+        while p != NIL {
+            self.node_mut(p).size -= 1;
+            p = self.node(p).parent;
+        }
+        self.len -= 1;
+        let node = self.dealloc(i);
+        (node.key, node.value.into_inner())
+    }
+
+    /// Returns the entry with the `k`-th smallest key, counting from zero.
+    pub fn nth(&self, mut k: usize) -> Option<(&K, &V)> {
+        let mut i = self.root;
+        while i != NIL {
+            let n = self.node(i);
+            let left = self.size_of(n.left) as usize;
+            match k.cmp(&left) {
+                Ordering::Less => i = n.left,
+                Ordering::Equal => return Some((&n.key, n.value())),
+                Ordering::Greater => {
+                    k -= left + 1;
+                    i = n.right;
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the number of keys in the map that are smaller than `key`.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut rank = 0;
+        let mut i = self.root;
+        while i != NIL {
+            let n = self.node(i);
+            if self.cmp.compare(key, n.key.borrow()).is_le() {
+                i = n.left;
+            } else {
+                rank += self.size_of(n.left) as usize + 1;
+                i = n.right;
+            }
+        }
+        rank
+    }
+
+    fn leftmost(&self, mut i: Idx) -> Idx {
+        while i != NIL && self.node(i).left != NIL {
+            i = self.node(i).left;
+        }
+        i
+    }
+
+    fn rightmost(&self, mut i: Idx) -> Idx {
+        while i != NIL && self.node(i).right != NIL {
+            i = self.node(i).right;
+        }
+        i
+    }
+
+    fn successor(&self, mut i: Idx) -> Idx {
+        if self.node(i).right != NIL {
+            return self.leftmost(self.node(i).right);
+        }
+        loop {
+            let p = self.node(i).parent;
+            if p == NIL || self.node(p).left == i {
+                return p;
+            }
+            i = p;
+        }
+    }
+
+    fn predecessor(&self, mut i: Idx) -> Idx {
+        if self.node(i).left != NIL {
+            return self.rightmost(self.node(i).left);
+        }
+        loop {
+            let p = self.node(i).parent;
+            if p == NIL || self.node(p).right == i {
+                return p;
+            }
+            i = p;
+        }
+    }
+
+    /// Returns an iterator over the entries of the map, sorted by key.
+    pub fn iter(&self) -> Iter<'_, K, V, C> {
+        Iter {
+            map: self,
+            front: self.leftmost(self.root),
+            back: self.rightmost(self.root),
+            len: self.len,
+        }
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator + '_ {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator + '_ {
+        self.iter().map(|(_, v)| v)
+    }
+
+    pub fn values_mut(
+        &mut self,
+    ) -> impl DoubleEndedIterator<Item = &mut V> + ExactSizeIterator + '_ {
+        self.iter_mut().map(|(_, v)| v)
+    }
+
+    /// Returns an iterator over the entries of the map, sorted by key, with mutable references
+    /// to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, C> {
+        IterMut {
+            inner: self.iter(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a double ended iterator over the entries whose keys lie in `range`, sorted by key.
+    ///
+    /// Panics if the start of the range is greater than its end.
+    pub fn range<Q, R>(&self, range: R) -> Iter<'_, K, V, C>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        R: RangeBounds<Q>,
+    {
+        check_range(&self.cmp, &range);
+        let (mut front, mut back) = (NIL, NIL);
+        let mut i = self.root;
+        while i != NIL {
+            let key = self.node(i).key.borrow();
+            let inside = match range.start_bound() {
+                Bound::Included(s) => self.cmp.compare(key, s).is_ge(),
+                Bound::Excluded(s) => self.cmp.compare(key, s).is_gt(),
+                Bound::Unbounded => true,
+            };
+            if inside {
+                front = i;
+                i = self.node(i).left;
+            } else {
+                i = self.node(i).right;
+            }
+        }
+        i = self.root;
+        while i != NIL {
+            let key = self.node(i).key.borrow();
+            let inside = match range.end_bound() {
+                Bound::Included(e) => self.cmp.compare(key, e).is_le(),
+                Bound::Excluded(e) => self.cmp.compare(key, e).is_lt(),
+                Bound::Unbounded => true,
+            };
+            if inside {
+                back = i;
+                i = self.node(i).right;
+            } else {
+                i = self.node(i).left;
+            }
+        }
+        let len = if front == NIL || back == NIL {
+            0
+        } else {
+            let (first, last) = (self.node(front).key.borrow(), self.node(back).key.borrow());
+            (self.rank(last) + 1).saturating_sub(self.rank(first))
+        };
+        Iter {
+            map: self,
+            front,
+            back,
+            len,
+        }
+    }
+
+    /// Returns a double ended iterator over the entries whose keys lie in `range`, sorted by key,
+    /// with mutable references to the values.
+    ///
+    /// Panics if the start of the range is greater than its end.
+    pub fn range_mut<Q, R>(&mut self, range: R) -> IterMut<'_, K, V, C>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        R: RangeBounds<Q>,
+    {
+        IterMut {
+            inner: self.range(range),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K, V, C: Comparator<K>> ArenaTreeMap<K, V, C> {
+    /// Checks the structural invariants of the treap, returning the first violation found.
+    pub fn validate(&self) -> Result<(), InvariantError<'_, K>> {
+        let mut count = 0;
+        // Every node together with its expected parent and the keys it has to lie between.
+        let mut stack = Vec::new();
+        if self.root != NIL {
+            stack.push((self.root, NIL, None, None));
+        }
+        while let Some((i, parent, low, high)) = stack.pop() {
+            let n = self.node(i);
+            let key = &n.key;
+            count += 1;
+            if n.parent != parent {
+                return Err(InvariantError::ParentLink { key });
+            }
+            if low.is_some_and(|low| self.cmp.compare(low, key).is_ge())
+                || high.is_some_and(|high| self.cmp.compare(key, high).is_ge())
+            {
+                return Err(InvariantError::KeyOrder { key });
+            }
+            if parent != NIL && self.node(parent).priority < n.priority {
+                return Err(InvariantError::HeapOrder { key });
+            }
+            let expected = 1 + self.size_of(n.left) + self.size_of(n.right);
+            if n.size != expected {
+                return Err(InvariantError::Size {
+                    key,
+                    expected: expected as usize,
+                    found: n.size as usize,
+                });
+            }
+            if n.right != NIL {
+                stack.push((n.right, i, Some(key), high));
+            }
+            if n.left != NIL {
+                stack.push((n.left, i, low, Some(key)));
+            }
+        }
+        if count != self.len {
+            return Err(InvariantError::Len {
+                expected: count,
+                found: self.len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Inserts the entry, returning the previous value if the key was already present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut o) => Some(o.insert(value)),
+            Entry::Vacant(v) => {
+                v.insert(value);
+                None
+            }
+        }
+    }
+
+    /// Returns the entry for `key`, which allows to inspect and update it with a single
+    /// descent.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, C> {
+        match self.search(|k| self.cmp.compare(k, &key)) {
+            Ok(i) => Entry::Occupied(OccupiedEntry { map: self, i }),
+            Err((parent, left)) => Entry::Vacant(VacantEntry {
+                map: self,
+                key,
+                parent,
+                left,
+            }),
+        }
+    }
+
+    /// Moves all entries of `other` into the map, keeping the values of `other` for keys
+    /// present in both.
+    ///
+    /// The entries of `other` move into the arena of `self`, which takes O(m) time for the m
+    /// entries of `other`, and are then merged in O(m log(n / m)) expected time.
+    pub fn append(&mut self, other: &mut Self) {
+        let b = self.move_from(other, other.root);
+        other.clear();
+        let root = self.union_at(self.root, b, true);
+        self.set_root(root);
+    }
+
+    /// Joins two maps by moving the entries of the smaller map into the arena of the larger
+    /// one, in O(m + log n) expected time for maps of sizes m <= n.
+    ///
+    /// Unlike [`RTreeMap::join`](super::RTreeMap::join), which relinks the two trees in
+    /// O(log n), the nodes of two arenas cannot link to each other, so the smaller map has to
+    /// be copied over.
+    ///
+    /// Panics if the keys of `left` are not all smaller than the keys of `right`.
+    pub fn join(mut left: Self, mut right: Self) -> Self {
+        let (max, min) = (left.rightmost(left.root), right.leftmost(right.root));
+        if max != NIL
+            && min != NIL
+            && left
+                .cmp
+                .compare(&left.node(max).key, &right.node(min).key)
+                .is_ge()
+        {
+            panic!("left map overlaps the right map");
+        }
+        if left.len < right.len {
+            let root = left.root;
+            let a = right.move_from(&mut left, root);
+            let root = right.merge(a, right.root);
+            right.set_root(root);
+            return right;
+        }
+        let root = right.root;
+        let b = left.move_from(&mut right, root);
+        let root = left.merge(left.root, b);
+        left.set_root(root);
+        left
+    }
+
+    /// Merges two possibly overlapping maps, keeping the values of `other` for keys present in
+    /// both. See [`ArenaTreeMap::append`].
+    pub fn union(mut self, mut other: Self) -> Self {
+        self.append(&mut other);
+        self
+    }
+
+    /// Splits the subtree at `i` into the keys before the key of `pivot`, the node holding an
+    /// equal key if there is one, and the keys after it.
+    fn split_at(&mut self, i: Idx, pivot: Idx) -> (Idx, Idx, Idx) {
+        let (left, rest) = self.split_by(i, &|map, j| {
+            map.cmp
+                .compare(&map.node(j).key, &map.node(pivot).key)
+                .is_lt()
+        });
+        let (middle, right) = self.split_by(rest, &|map, j| {
+            map.cmp
+                .compare(&map.node(j).key, &map.node(pivot).key)
+                .is_le()
+        });
+        (left, middle, right)
+    }
+
+    /// Merges two arbitrary subtrees, keeping the value of `b` for duplicate keys if `b_wins`.
+    fn union_at(&mut self, a: Idx, b: Idx, b_wins: bool) -> Idx {
+        if a == NIL {
+            return b;
+        }
+        if b == NIL {
+            return a;
+        }
+        if self.node(a).priority < self.node(b).priority {
+            return self.union_at(b, a, !b_wins);
+        }
+        let (l, m, r) = self.split_at(b, a);
+        if m != NIL {
+            let m = self.dealloc(m);
+            if b_wins {
+                self.node_mut(a).value = m.value;
+            }
+        }
+        let left = self.union_at(self.node(a).left, l, b_wins);
+        let right = self.union_at(self.node(a).right, r, b_wins);
+        self.set_children(a, left, right);
+        a
+    }
+}
+
+impl<K, V, C: Default> Default for ArenaTreeMap<K, V, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+impl<K, V, C: Comparator<K> + Default> FromIterator<(K, V)> for ArenaTreeMap<K, V, C> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, C: Comparator<K>> Extend<(K, V)> for ArenaTreeMap<K, V, C> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, C> Debug for ArenaTreeMap<K, V, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a ArenaTreeMap<K, V, C> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a mut ArenaTreeMap<K, V, C> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, C> IntoIterator for ArenaTreeMap<K, V, C> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            front: self.leftmost(self.root),
+            back: self.rightmost(self.root),
+            map: self,
+        }
+    }
+}
+
+pub enum Entry<'a, K, V, C = Natural> {
+    Occupied(OccupiedEntry<'a, K, V, C>),
+    Vacant(VacantEntry<'a, K, V, C>),
+}
+
+impl<'a, K, V, C> Entry<'a, K, V, C> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(o) => o.key(),
+            Entry::Vacant(v) => v.key(),
+        }
+    }
+
+    /// Inserts `default` if the entry is vacant and returns a mutable reference to the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(o) => o.into_mut(),
+            Entry::Vacant(v) => v.insert(default),
+        }
+    }
+
+    /// Inserts the result of `default` if the entry is vacant and returns a mutable reference
+    /// to the value.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(o) => o.into_mut(),
+            Entry::Vacant(v) => v.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` on the value if the entry is occupied.
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(o) = &mut self {
+            f(o.get_mut());
+        }
+        self
+    }
+}
+
+pub struct OccupiedEntry<'a, K, V, C = Natural> {
+    map: &'a mut ArenaTreeMap<K, V, C>,
+    i: Idx,
+}
+
+impl<'a, K, V, C> OccupiedEntry<'a, K, V, C> {
+    pub fn key(&self) -> &K {
+        &self.map.node(self.i).key
+    }
+
+    pub fn get(&self) -> &V {
+        self.map.node(self.i).value()
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.map.node_mut(self.i).value.get_mut()
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        self.map.node_mut(self.i).value.get_mut()
+    }
+
+    /// Replaces the value of the entry, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the entry from the map, returning its key and value.
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.remove_at(self.i)
+    }
+}
+
+pub struct VacantEntry<'a, K, V, C = Natural> {
+    map: &'a mut ArenaTreeMap<K, V, C>,
+    key: K,
+    /// The node to link the new node below, on its left if `left`.
+    parent: Idx,
+    left: bool,
+}
+
+impl<'a, K, V, C> VacantEntry<'a, K, V, C> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the entry with `value` and returns a mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let i = self.map.insert_at(self.parent, self.left, self.key, value);
+        self.map.node_mut(i).value.get_mut()
+    }
+}
+
+/// In order iterator over the entries of an [`ArenaTreeMap`].
+pub struct Iter<'a, K, V, C> {
+    map: &'a ArenaTreeMap<K, V, C>,
+    front: Idx,
+    back: Idx,
+    len: usize,
+}
+
+impl<'a, K, V, C> Iter<'a, K, V, C> {
+    fn next_index(&mut self) -> Option<Idx> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let i = self.front;
+        self.front = self.map.successor(i);
+        Some(i)
+    }
+
+    fn next_back_index(&mut self) -> Option<Idx> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let i = self.back;
+        self.back = self.map.predecessor(i);
+        Some(i)
+    }
+}
+
+impl<'a, K, V, C> Iterator for Iter<'a, K, V, C> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.map.node(self.next_index()?);
+        Some((&n.key, n.value()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, K, V, C> DoubleEndedIterator for Iter<'a, K, V, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let n = self.map.node(self.next_back_index()?);
+        Some((&n.key, n.value()))
+    }
+}
+
+impl<'a, K, V, C> ExactSizeIterator for Iter<'a, K, V, C> {}
+impl<'a, K, V, C> FusedIterator for Iter<'a, K, V, C> {}
+
+/// In order iterator over the entries of an [`ArenaTreeMap`] with mutable references to the
+/// values.
+pub struct IterMut<'a, K, V, C> {
+    /// Walks the map through a shared reference, which is fine as long as nothing but the
+    /// values is written to.
+    inner: Iter<'a, K, V, C>,
+    _marker: PhantomData<&'a mut V>,
+}
+
+impl<'a, K, V, C> Iterator for IterMut<'a, K, V, C> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.inner.map.node(self.inner.next_index()?);
+        // SAFETY: The iterator borrows the map mutably and yields every node at most once, so
+        // this is the only reference to the value. `inner` only reads the links of the nodes.
+        Some((&n.key, unsafe { &mut *n.value.get() }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, C> DoubleEndedIterator for IterMut<'a, K, V, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let n = self.inner.map.node(self.inner.next_back_index()?);
+        // SAFETY: As in `next`, the two ends never yield the same node.
+        Some((&n.key, unsafe { &mut *n.value.get() }))
+    }
+}
+
+impl<'a, K, V, C> ExactSizeIterator for IterMut<'a, K, V, C> {}
+impl<'a, K, V, C> FusedIterator for IterMut<'a, K, V, C> {}
+
+/// Owning in order iterator over the entries of an [`ArenaTreeMap`].
+///
+/// Every step unlinks the smallest (or largest) remaining node, which never has more than one
+/// child, so the links of the remaining nodes stay valid.
+pub struct IntoIter<K, V, C> {
+    map: ArenaTreeMap<K, V, C>,
+    front: Idx,
+    back: Idx,
+}
+
+impl<K, V, C> IntoIter<K, V, C> {
+    /// Replaces `i`, which has at most one child, by that child and frees it.
+    fn unlink(&mut self, i: Idx) -> (K, V) {
+        let map = &mut self.map;
+        let (parent, left, right) = {
+            let n = map.node(i);
+            (n.parent, n.left, n.right)
+        };
+        let child = if left == NIL { right } else { left };
+        if child != NIL {
+            map.node_mut(child).parent = parent;
+        }
+        if parent == NIL {
+            map.root = child;
+        } else if map.node(parent).left == i {
+            map.node_mut(parent).left = child;
+        } else {
+            map.node_mut(parent).right = child;
+        }
+        map.len -= 1;
+        let node = map.dealloc(i);
+        (node.key, node.value.into_inner())
+    }
+}
+
+impl<K, V, C> Iterator for IntoIter<K, V, C> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.map.len == 0 {
+            return None;
+        }
+        let i = self.front;
+        self.front = self.map.successor(i);
+        Some(self.unlink(i))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.map.len, Some(self.map.len))
+    }
+}
+
+impl<K, V, C> DoubleEndedIterator for IntoIter<K, V, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.map.len == 0 {
+            return None;
+        }
+        let i = self.back;
+        self.back = self.map.predecessor(i);
+        Some(self.unlink(i))
+    }
+}
+
+impl<K, V, C> ExactSizeIterator for IntoIter<K, V, C> {}
+impl<K, V, C> FusedIterator for IntoIter<K, V, C> {}
@@ -0,0 +1,823 @@
+use crate::{
+    compare::{Comparator, Natural},
+    monoid::Monoid,
+};
+use rand::{distributions::uniform::SampleUniform, thread_rng, Rng};
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt::Debug,
+    iter::FusedIterator,
+    mem,
+    ops::{Bound, RangeBounds},
+};
+
+/// An ordered map backed by a treap whose nodes cache the [`Monoid`] summary of the values in
+/// their subtree, so the summary of any key range takes O(log n) expected time.
+///
+/// Values can only be changed through [`AugmentedTreeMap::insert`] and
+/// [`AugmentedTreeMap::update`], which keep the cached summaries up to date.
+pub struct AugmentedTreeMap<K, V, M: Monoid<V>, C = Natural> {
+    root: Link<K, V, M::Summary>,
+    len: usize,
+    monoid: M,
+    cmp: C,
+}
+
+impl<K: Debug, V: Debug, M: Monoid<V>, C> Debug for AugmentedTreeMap<K, V, M, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, M: Monoid<V>> AugmentedTreeMap<K, V, M> {
+    /// Creates an empty map that summarizes its values with `monoid`.
+    pub fn new(monoid: M) -> Self {
+        Self::with_comparator(monoid, Natural)
+    }
+}
+
+impl<K, V, M: Monoid<V>, C> AugmentedTreeMap<K, V, M, C> {
+    /// Creates an empty map that summarizes its values with `monoid` and orders its keys by
+    /// `cmp`.
+    pub fn with_comparator(monoid: M, cmp: C) -> Self {
+        Self {
+            root: None,
+            len: 0,
+            monoid,
+            cmp,
+        }
+    }
+
+    pub fn monoid(&self) -> &M {
+        &self.monoid
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            match self.cmp.compare(n.key.borrow(), key) {
+                Ordering::Greater => node = n.left.as_deref(),
+                Ordering::Equal => return Some(&n.value),
+                Ordering::Less => node = n.right.as_deref(),
+            }
+        }
+        None
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let removed = Node::remove(&mut self.root, key, &self.monoid, &self.cmp);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Calls `f` on the value for `key` and refreshes the cached summaries above it. Returns
+    /// whether the key was present.
+    pub fn update<Q, F>(&mut self, key: &Q, f: F) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        F: FnOnce(&mut V),
+    {
+        Node::update_value(&mut self.root, key, f, &self.monoid, &self.cmp)
+    }
+
+    /// Returns the summary of all values in the map.
+    pub fn total(&self) -> M::Summary {
+        match &self.root {
+            Some(r) => r.summary.clone(),
+            None => self.monoid.identity(),
+        }
+    }
+
+    /// Returns the summary of the values whose keys lie in `range`, combined in key order.
+    pub fn aggregate<Q, R>(&self, range: R) -> M::Summary
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        R: RangeBounds<Q>,
+    {
+        Node::aggregate(
+            self.root.as_deref(),
+            range.start_bound(),
+            range.end_bound(),
+            &self.monoid,
+            &self.cmp,
+        )
+    }
+
+    /// Returns the first entry, in key order, at which the summary of the values up to and
+    /// including it satisfies `pred`, or `None` if the summary of the whole map does not.
+    ///
+    /// `pred` has to be monotone, i.e. once it holds for a prefix it holds for every longer
+    /// prefix, like a running sum exceeding a threshold. Takes O(log n) expected time.
+    pub fn search_prefix<F>(&self, mut pred: F) -> Option<(&K, &V)>
+    where
+        F: FnMut(&M::Summary) -> bool,
+    {
+        let mut acc = self.monoid.identity();
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            if let Some(l) = &n.left {
+                let with_left = self.monoid.combine(&acc, &l.summary);
+                if pred(&with_left) {
+                    node = Some(l);
+                    continue;
+                }
+                acc = with_left;
+            }
+            acc = self.monoid.combine(&acc, &self.monoid.lift(&n.value));
+            if pred(&acc) {
+                return Some((&n.key, &n.value));
+            }
+            node = n.right.as_deref();
+        }
+        None
+    }
+
+    /// Returns an entry chosen at random with a probability proportional to its summary, for
+    /// example with [`Sum`](crate::monoid::Sum) over weights or [`Weight`]. Returns `None` if
+    /// the total is not above the identity.
+    ///
+    /// Draws a point below the total and finds the entry whose prefix passes it, so every
+    /// summary has to be at least the identity. Takes O(log n) expected time.
+    ///
+    /// [`Weight`]: crate::monoid::Weight
+    pub fn choose_weighted<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<(&K, &V)>
+    where
+        M::Summary: SampleUniform + PartialOrd,
+    {
+        let total = self.total();
+        let zero = self.monoid.identity();
+        if zero.partial_cmp(&total) != Some(Ordering::Less) {
+            return None;
+        }
+        let point = rng.gen_range(zero.clone()..total);
+        // With floating point weights the prefixes can round to slightly less than the cached
+        // total, which leaves a point right below it without a match. It belongs to the last
+        // entry that has a weight at all.
+        self.search_prefix(|prefix| *prefix > point)
+            .or_else(|| self.last_above(&zero))
+    }
+
+    /// Returns the last entry whose summary is above `zero`, given that no summary is below it.
+    fn last_above(&self, zero: &M::Summary) -> Option<(&K, &V)>
+    where
+        M::Summary: PartialOrd,
+    {
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            if let Some(r) = n.right.as_deref().filter(|r| r.summary > *zero) {
+                node = Some(r);
+            } else if self.monoid.lift(&n.value) > *zero {
+                return Some((&n.key, &n.value));
+            } else {
+                node = n.left.as_deref();
+            }
+        }
+        None
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(l) = node.left.as_deref() {
+            node = l;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(r) = node.right.as_deref() {
+            node = r;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    /// Returns the entry with the largest key less than or equal to `key`.
+    pub fn floor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.closest(key, Ordering::is_le, true)
+    }
+
+    /// Returns the entry with the smallest key greater than or equal to `key`.
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.closest(key, Ordering::is_ge, false)
+    }
+
+    /// Returns the entry with the largest key strictly less than `key`.
+    pub fn predecessor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.closest(key, Ordering::is_lt, true)
+    }
+
+    /// Returns the entry with the smallest key strictly greater than `key`.
+    pub fn successor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.closest(key, Ordering::is_gt, false)
+    }
+
+    /// Returns the entry closest to `key` among those whose keys compare to it as `fits`
+    /// accepts, looking below `key` if `below` and above it otherwise.
+    fn closest<Q, F>(&self, key: &Q, fits: F, below: bool) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        F: Fn(Ordering) -> bool,
+    {
+        let mut best = None;
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            let fit = fits(self.cmp.compare(n.key.borrow(), key));
+            if fit {
+                best = Some((&n.key, &n.value));
+            }
+            node = if fit == below {
+                n.right.as_deref()
+            } else {
+                n.left.as_deref()
+            };
+        }
+        best
+    }
+
+    /// Splits the map in two at `key`, returning everything after and including `key`.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        M: Clone,
+        C: Comparator<Q> + Clone,
+    {
+        let (left, right) = Node::split_by(
+            self.root.take(),
+            &|k: &K| self.cmp.compare(k.borrow(), key).is_lt(),
+            &self.monoid,
+        );
+        self.set_root(left);
+        let mut other = Self::with_comparator(self.monoid.clone(), self.cmp.clone());
+        other.set_root(right);
+        other
+    }
+
+    /// Makes `root` the root of the map, updating the length accordingly.
+    fn set_root(&mut self, root: Link<K, V, M::Summary>) {
+        self.len = Node::size_of(&root);
+        self.root = root;
+    }
+
+    /// Returns the root node, so that the collections built on the map can search it by the
+    /// cached summaries.
+    pub(crate) fn root(&self) -> Option<&Node<K, V, M::Summary>> {
+        self.root.as_deref()
+    }
+
+    /// Returns an iterator over the entries, sorted by key.
+    pub fn iter(&self) -> Iter<'_, K, V, M::Summary> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            len: self.len,
+        };
+        iter.push_left(self.root.as_deref());
+        iter
+    }
+}
+
+impl<K, V, M: Monoid<V>, C: Comparator<K>> AugmentedTreeMap<K, V, M, C> {
+    /// Inserts the entry, returning the previous value for `key` if there was one.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let priority = thread_rng().gen();
+        let old = Node::insert(
+            &mut self.root,
+            key,
+            value,
+            priority,
+            &self.monoid,
+            &self.cmp,
+        );
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Moves all entries of `other` into the map, keeping the values of `other` for keys
+    /// present in both.
+    pub fn append(&mut self, other: &mut Self) {
+        self.merge_with(other, (true, true), |_, value, theirs| {
+            *value = theirs;
+            true
+        });
+    }
+
+    /// Joins two maps in O(log n) expected time.
+    ///
+    /// Panics if the keys of `left` are not all smaller than the keys of `right`.
+    pub fn join(mut left: Self, mut right: Self) -> Self {
+        if let (Some((max, _)), Some((min, _))) = (left.last_key_value(), right.first_key_value()) {
+            if left.cmp.compare(max, min).is_ge() {
+                panic!("left map overlaps the right map");
+            }
+        }
+        let root = Node::merge(left.root.take(), right.root.take(), &left.monoid);
+        left.set_root(root);
+        left
+    }
+
+    /// Merges two possibly overlapping maps, keeping the values of `other` for keys present in
+    /// both. Takes O(m log(n / m)) expected time for maps of sizes m <= n.
+    pub fn union(mut self, mut other: Self) -> Self {
+        self.append(&mut other);
+        self
+    }
+
+    /// Keeps the entries of `self` whose keys are also present in `other`. Takes
+    /// O(m log(n / m)) expected time for maps of sizes m <= n.
+    pub fn intersection(mut self, mut other: Self) -> Self {
+        self.merge_with(&mut other, (false, false), |_, _, _| true);
+        self
+    }
+
+    /// Keeps the entries of `self` whose keys are missing from `other`. Takes
+    /// O(m log(n / m)) expected time for maps of sizes m <= n.
+    pub fn difference(mut self, mut other: Self) -> Self {
+        self.merge_with(&mut other, (true, false), |_, _, _| false);
+        self
+    }
+
+    /// Keeps the entries whose keys are present in exactly one of the maps. Takes
+    /// O(m log(n / m)) expected time for maps of sizes m <= n.
+    pub fn symmetric_difference(mut self, mut other: Self) -> Self {
+        self.merge_with(&mut other, (true, true), |_, _, _| false);
+        self
+    }
+
+    /// Moves all entries of `other` into the map. Keys present in both keep the key of `self`
+    /// with the value updated by `f(key, value, value of other)`, or are removed if `f`
+    /// returns `false`. Keys present in only one of the maps are kept if the matching flag of
+    /// `keep` is set.
+    pub(crate) fn merge_with<F>(&mut self, other: &mut Self, keep: (bool, bool), mut f: F)
+    where
+        F: FnMut(&K, &mut V, V) -> bool,
+    {
+        let (a, b) = (self.root.take(), other.root.take());
+        other.len = 0;
+        let root = Node::merge_with(a, b, keep, false, &mut f, &self.monoid, &self.cmp);
+        self.set_root(root);
+    }
+}
+
+impl<K, V, M: Monoid<V> + Default, C: Default> Default for AugmentedTreeMap<K, V, M, C> {
+    fn default() -> Self {
+        Self::with_comparator(M::default(), C::default())
+    }
+}
+
+impl<K, V, M: Monoid<V>, C> Drop for AugmentedTreeMap<K, V, M, C> {
+    fn drop(&mut self) {
+        Node::drop_tree(self.root.take());
+    }
+}
+
+impl<'a, K, V, M: Monoid<V>, C> IntoIterator for &'a AugmentedTreeMap<K, V, M, C> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, M::Summary>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// In order iterator over the entries of an [`AugmentedTreeMap`].
+pub struct Iter<'a, K, V, S> {
+    stack: Vec<&'a Node<K, V, S>>,
+    len: usize,
+}
+
+impl<'a, K, V, S> Iter<'a, K, V, S> {
+    fn push_left(&mut self, mut node: Option<&'a Node<K, V, S>>) {
+        while let Some(n) = node {
+            self.stack.push(n);
+            node = n.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K, V, S> Iterator for Iter<'a, K, V, S> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.len -= 1;
+        self.push_left(node.right.as_deref());
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, K, V, S> ExactSizeIterator for Iter<'a, K, V, S> {}
+impl<'a, K, V, S> FusedIterator for Iter<'a, K, V, S> {}
+
+type Link<K, V, S> = Option<Box<Node<K, V, S>>>;
+
+pub(crate) struct Node<K, V, S> {
+    key: K,
+    value: V,
+    priority: usize,
+    /// Number of nodes in the subtree rooted at this node.
+    size: usize,
+    /// Summary of the values in the subtree rooted at this node.
+    summary: S,
+    left: Link<K, V, S>,
+    right: Link<K, V, S>,
+}
+
+impl<K, V, S> Node<K, V, S> {
+    pub(crate) fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub(crate) fn value(&self) -> &V {
+        &self.value
+    }
+
+    pub(crate) fn summary(&self) -> &S {
+        &self.summary
+    }
+
+    pub(crate) fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    pub(crate) fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+impl<K, V, S: Clone> Node<K, V, S> {
+    /// Recomputes the summary from the children, which have to be up to date already.
+    fn update<M: Monoid<V, Summary = S>>(&mut self, monoid: &M) {
+        let mut summary = monoid.lift(&self.value);
+        if let Some(l) = &self.left {
+            summary = monoid.combine(&l.summary, &summary);
+        }
+        if let Some(r) = &self.right {
+            summary = monoid.combine(&summary, &r.summary);
+        }
+        self.summary = summary;
+        self.size = 1 + Self::size_of(&self.left) + Self::size_of(&self.right);
+    }
+
+    /// Lifts the left child of the node in `link` above it.
+    fn rotate_right<M: Monoid<V, Summary = S>>(link: &mut Link<K, V, S>, monoid: &M) {
+        let Some(mut node) = link.take() else {
+            return;
+        };
+        let Some(mut new_parent) = node.left.take() else {
+            *link = Some(node);
+            return;
+        };
+        node.left = new_parent.right.take();
+        node.update(monoid);
+        new_parent.right = Some(node);
+        new_parent.update(monoid);
+        *link = Some(new_parent);
+    }
+
+    /// Lifts the right child of the node in `link` above it.
+    fn rotate_left<M: Monoid<V, Summary = S>>(link: &mut Link<K, V, S>, monoid: &M) {
+        let Some(mut node) = link.take() else {
+            return;
+        };
+        let Some(mut new_parent) = node.right.take() else {
+            *link = Some(node);
+            return;
+        };
+        node.right = new_parent.left.take();
+        node.update(monoid);
+        new_parent.left = Some(node);
+        new_parent.update(monoid);
+        *link = Some(new_parent);
+    }
+
+    fn size_of(link: &Link<K, V, S>) -> usize {
+        link.as_ref().map_or(0, |n| n.size)
+    }
+
+    fn priority_of(link: &Link<K, V, S>) -> usize {
+        link.as_ref().map_or(0, |n| n.priority)
+    }
+
+    /// Inserts the entry below `link` and rotates it up along the way back until the heap order
+    /// holds again.
+    fn insert<M, C>(
+        link: &mut Link<K, V, S>,
+        key: K,
+        value: V,
+        priority: usize,
+        monoid: &M,
+        cmp: &C,
+    ) -> Option<V>
+    where
+        M: Monoid<V, Summary = S>,
+        C: Comparator<K>,
+    {
+        let Some(node) = link.as_deref_mut() else {
+            *link = Some(Box::new(Node {
+                summary: monoid.lift(&value),
+                key,
+                value,
+                priority,
+                size: 1,
+                left: None,
+                right: None,
+            }));
+            return None;
+        };
+        let old = match cmp.compare(&key, &node.key) {
+            Ordering::Equal => Some(mem::replace(&mut node.value, value)),
+            Ordering::Less => Self::insert(&mut node.left, key, value, priority, monoid, cmp),
+            Ordering::Greater => Self::insert(&mut node.right, key, value, priority, monoid, cmp),
+        };
+        node.update(monoid);
+        if Self::priority_of(&node.left) > node.priority {
+            Self::rotate_right(link, monoid);
+        } else if Self::priority_of(&node.right) > node.priority {
+            Self::rotate_left(link, monoid);
+        }
+        old
+    }
+
+    fn update_value<Q, F, M, C>(
+        link: &mut Link<K, V, S>,
+        key: &Q,
+        f: F,
+        monoid: &M,
+        cmp: &C,
+    ) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        F: FnOnce(&mut V),
+        M: Monoid<V, Summary = S>,
+        C: Comparator<Q>,
+    {
+        let Some(node) = link.as_deref_mut() else {
+            return false;
+        };
+        let found = match cmp.compare(node.key.borrow(), key) {
+            Ordering::Greater => Self::update_value(&mut node.left, key, f, monoid, cmp),
+            Ordering::Less => Self::update_value(&mut node.right, key, f, monoid, cmp),
+            Ordering::Equal => {
+                f(&mut node.value);
+                true
+            }
+        };
+        if found {
+            node.update(monoid);
+        }
+        found
+    }
+
+    fn remove<Q, M, C>(link: &mut Link<K, V, S>, key: &Q, monoid: &M, cmp: &C) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        M: Monoid<V, Summary = S>,
+        C: Comparator<Q>,
+    {
+        let node = link.as_deref_mut()?;
+        let removed = match cmp.compare(node.key.borrow(), key) {
+            Ordering::Greater => Self::remove(&mut node.left, key, monoid, cmp)?,
+            Ordering::Less => Self::remove(&mut node.right, key, monoid, cmp)?,
+            Ordering::Equal => {
+                let mut node = link.take()?;
+                *link = Self::merge(node.left.take(), node.right.take(), monoid);
+                return Some(node.value);
+            }
+        };
+        node.update(monoid);
+        Some(removed)
+    }
+
+    /// Joins two subtrees where every key of `a` is less than every key of `b`.
+    fn merge<M: Monoid<V, Summary = S>>(
+        a: Link<K, V, S>,
+        b: Link<K, V, S>,
+        monoid: &M,
+    ) -> Link<K, V, S> {
+        match (a, b) {
+            (None, b) => b,
+            (a, None) => a,
+            (Some(mut x), Some(mut y)) => Some(if x.priority > y.priority {
+                x.right = Self::merge(x.right.take(), Some(y), monoid);
+                x.update(monoid);
+                x
+            } else {
+                y.left = Self::merge(Some(x), y.left.take(), monoid);
+                y.update(monoid);
+                y
+            }),
+        }
+    }
+
+    /// Unlinks the nodes one by one so that dropping a deep tree does not recurse.
+    fn drop_tree(link: Link<K, V, S>) {
+        let mut stack = Vec::from_iter(link);
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+
+    /// Splits the subtree into the keys for which `is_left` holds and the keys after them.
+    ///
+    /// `is_left` has to hold for a prefix of the keys.
+    fn split_by<M, F>(
+        link: Link<K, V, S>,
+        is_left: &F,
+        monoid: &M,
+    ) -> (Link<K, V, S>, Link<K, V, S>)
+    where
+        M: Monoid<V, Summary = S>,
+        F: Fn(&K) -> bool,
+    {
+        let Some(mut node) = link else {
+            return (None, None);
+        };
+        if is_left(&node.key) {
+            let (l, r) = Self::split_by(node.right.take(), is_left, monoid);
+            node.right = l;
+            node.update(monoid);
+            (Some(node), r)
+        } else {
+            let (l, r) = Self::split_by(node.left.take(), is_left, monoid);
+            node.left = r;
+            node.update(monoid);
+            (l, Some(node))
+        }
+    }
+
+    /// Merges two arbitrary subtrees, pivoting on the node with the higher priority.
+    ///
+    /// Keys present in both subtrees keep the entry of `a` with its value updated by
+    /// `f(key, value of a, value of b)`, or are dropped if `f` returns `false`. Keys present in
+    /// only one of them are kept if the matching flag of `keep` is set. With `swapped` the
+    /// subtrees trade places and so do the arguments of `f` and the flags.
+    fn merge_with<M, C, F>(
+        a: Link<K, V, S>,
+        b: Link<K, V, S>,
+        keep: (bool, bool),
+        swapped: bool,
+        f: &mut F,
+        monoid: &M,
+        cmp: &C,
+    ) -> Link<K, V, S>
+    where
+        M: Monoid<V, Summary = S>,
+        C: Comparator<K>,
+        F: FnMut(&K, &mut V, V) -> bool,
+    {
+        let (mut a, b) = match (a, b) {
+            (Some(a), Some(b)) => (a, b),
+            (a, None) => {
+                if keep.0 {
+                    return a;
+                }
+                Self::drop_tree(a);
+                return None;
+            }
+            (None, b) => {
+                if keep.1 {
+                    return b;
+                }
+                Self::drop_tree(b);
+                return None;
+            }
+        };
+        if a.priority < b.priority {
+            let keep = (keep.1, keep.0);
+            return Self::merge_with(Some(b), Some(a), keep, !swapped, f, monoid, cmp);
+        }
+        let (l, rest) = Self::split_by(Some(b), &|k| cmp.compare(k, &a.key).is_lt(), monoid);
+        let (m, r) = Self::split_by(rest, &|k| cmp.compare(k, &a.key).is_le(), monoid);
+        let left = Self::merge_with(a.left.take(), l, keep, swapped, f, monoid, cmp);
+        let right = Self::merge_with(a.right.take(), r, keep, swapped, f, monoid, cmp);
+        let kept = match m {
+            Some(mut m) => {
+                if swapped {
+                    mem::swap(&mut a.key, &mut m.key);
+                    mem::swap(&mut a.value, &mut m.value);
+                }
+                f(&a.key, &mut a.value, m.value)
+            }
+            None => keep.0,
+        };
+        if !kept {
+            return Self::merge(left, right, monoid);
+        }
+        a.left = left;
+        a.right = right;
+        a.update(monoid);
+        Some(a)
+    }
+
+    /// Combines the values of the subtree whose keys lie between `start` and `end`.
+    ///
+    /// Once a node inside the range is found, its left subtree only has a start bound and its
+    /// right subtree only an end bound, so either side walks down a single path and picks up
+    /// the cached summaries of the subtrees hanging off it.
+    fn aggregate<Q, M, C>(
+        node: Option<&Self>,
+        start: Bound<&Q>,
+        end: Bound<&Q>,
+        monoid: &M,
+        cmp: &C,
+    ) -> S
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        M: Monoid<V, Summary = S>,
+        C: Comparator<Q>,
+    {
+        let Some(n) = node else {
+            return monoid.identity();
+        };
+        if let (Bound::Unbounded, Bound::Unbounded) = (start, end) {
+            return n.summary.clone();
+        }
+        let key = n.key.borrow();
+        let below = match start {
+            Bound::Included(s) => cmp.compare(key, s).is_lt(),
+            Bound::Excluded(s) => cmp.compare(key, s).is_le(),
+            Bound::Unbounded => false,
+        };
+        if below {
+            return Self::aggregate(n.right.as_deref(), start, end, monoid, cmp);
+        }
+        let above = match end {
+            Bound::Included(e) => cmp.compare(key, e).is_gt(),
+            Bound::Excluded(e) => cmp.compare(key, e).is_ge(),
+            Bound::Unbounded => false,
+        };
+        if above {
+            return Self::aggregate(n.left.as_deref(), start, end, monoid, cmp);
+        }
+        let left = Self::aggregate(n.left.as_deref(), start, Bound::Unbounded, monoid, cmp);
+        let right = Self::aggregate(n.right.as_deref(), Bound::Unbounded, end, monoid, cmp);
+        let middle = monoid.combine(&left, &monoid.lift(&n.value));
+        monoid.combine(&middle, &right)
+    }
+}
@@ -2,6 +2,7 @@ use super::Alias;
 
 pub struct EventEmmiter<const S: usize, E> {
     alias: Alias<S>,
+    dist: [f64; S],
     events: [E; S],
 }
 
@@ -9,6 +10,7 @@ impl<const S: usize, E> EventEmmiter<S, E> {
     pub fn new(dist: &[f64; S], events: [E; S]) -> Self {
         Self {
             alias: Alias::new(dist),
+            dist: *dist,
             events,
         }
     }
@@ -23,3 +25,130 @@ impl<const S: usize, E: Clone> EventEmmiter<S, E> {
         self.events[self.alias.generate()].clone()
     }
 }
+
+/// Callback receiving the path, the leaf and the overall probability of a leaf.
+type LeafFn<'a, 'f, L> = dyn FnMut(&[usize], &'a L, f64) + 'f;
+
+/// Something that draws leaf events, possibly by descending through nested emitters.
+///
+/// An [`EventEmmiter`] whose events implement `Emitter` is itself an `Emitter`, which allows
+/// building trees of alias tables. Wrap plain events in [`Leaf`] and use `Box<dyn Emitter>` to
+/// mix subtrees of different sizes.
+pub trait Emitter {
+    type Leaf;
+
+    /// Draws a leaf, pushing the index chosen at every level onto `path`.
+    fn generate_into(&self, path: &mut Vec<usize>) -> &Self::Leaf;
+
+    /// Calls `f` with the path, the leaf and the overall probability of every leaf, given that
+    /// this emitter is reached with probability `p` under `path`.
+    fn for_each_leaf<'a>(
+        &'a self,
+        p: f64,
+        path: &mut Vec<usize>,
+        f: &mut LeafFn<'a, '_, Self::Leaf>,
+    );
+}
+
+/// A terminal event in a tree of emitters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leaf<E>(pub E);
+
+impl<E> Emitter for Leaf<E> {
+    type Leaf = E;
+
+    fn generate_into(&self, _: &mut Vec<usize>) -> &E {
+        &self.0
+    }
+
+    fn for_each_leaf<'a>(&'a self, p: f64, path: &mut Vec<usize>, f: &mut LeafFn<'a, '_, E>) {
+        f(path, &self.0, p)
+    }
+}
+
+impl<T: Emitter + ?Sized> Emitter for Box<T> {
+    type Leaf = T::Leaf;
+
+    fn generate_into(&self, path: &mut Vec<usize>) -> &Self::Leaf {
+        (**self).generate_into(path)
+    }
+
+    fn for_each_leaf<'a>(
+        &'a self,
+        p: f64,
+        path: &mut Vec<usize>,
+        f: &mut LeafFn<'a, '_, Self::Leaf>,
+    ) {
+        (**self).for_each_leaf(p, path, f)
+    }
+}
+
+impl<const S: usize, E: Emitter> Emitter for EventEmmiter<S, E> {
+    type Leaf = E::Leaf;
+
+    fn generate_into(&self, path: &mut Vec<usize>) -> &Self::Leaf {
+        let i = self.alias.generate();
+        path.push(i);
+        self.events[i].generate_into(path)
+    }
+
+    fn for_each_leaf<'a>(
+        &'a self,
+        p: f64,
+        path: &mut Vec<usize>,
+        f: &mut LeafFn<'a, '_, Self::Leaf>,
+    ) {
+        for (i, event) in self.events.iter().enumerate() {
+            path.push(i);
+            event.for_each_leaf(p * self.dist[i], path, f);
+            path.pop();
+        }
+    }
+}
+
+impl<const S: usize, E: Emitter> EventEmmiter<S, E> {
+    /// Descends the tree of emitters and returns the drawn leaf together with the index chosen
+    /// at every level.
+    pub fn generate_leaf(&self) -> (&E::Leaf, Vec<usize>) {
+        let mut path = Vec::new();
+        let leaf = self.generate_into(&mut path);
+        (leaf, path)
+    }
+
+    /// Returns every leaf with its path and its overall probability, i.e. the product of the
+    /// probabilities along its path.
+    pub fn leaf_probabilities(&self) -> Vec<(Vec<usize>, &E::Leaf, f64)> {
+        let mut leaves = Vec::new();
+        self.for_each_leaf(1.0, &mut Vec::new(), &mut |path, leaf, p| {
+            leaves.push((path.to_vec(), leaf, p))
+        });
+        leaves
+    }
+
+    /// Collapses the tree into a single table over its `N` leaves, drawing each leaf with the
+    /// same overall probability as the tree does.
+    ///
+    /// Panics if the tree does not have exactly `N` leaves.
+    pub fn flatten<const N: usize>(&self) -> EventEmmiter<N, &E::Leaf> {
+        let leaves = self.leaf_probabilities();
+        assert_eq!(leaves.len(), N, "tree has {} leaves", leaves.len());
+        let total: f64 = leaves.iter().map(|(_, _, p)| p).sum();
+        let mut dist = [0.0; N];
+        for (d, (_, _, p)) in dist.iter_mut().zip(leaves.iter()) {
+            *d = p / total;
+        }
+        let Ok(events) = <[&E::Leaf; N]>::try_from(
+            leaves
+                .into_iter()
+                .map(|(_, leaf, _)| leaf)
+                .collect::<Vec<_>>(),
+        ) else {
+            unreachable!("leaf count checked above")
+        };
+        EventEmmiter {
+            alias: Alias::new_unchecked(&dist),
+            dist,
+            events,
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use super::{augmented, AugmentedTreeMap};
+use crate::{compare::Comparator, monoid::Monoid};
+use std::{cmp::Ordering, fmt::Debug, iter::FusedIterator, ops::Range};
+
+/// A map from half open intervals `start..end` to values, backed by an [`AugmentedTreeMap`]
+/// ordered by `(start, end)` whose summaries are the largest `end` of each subtree.
+///
+/// The cached ends let queries skip every subtree that ends before the query begins, so a
+/// stabbing or overlap query only walks the paths leading to the k intervals it reports. Each
+/// path takes O(log n) expected time, which bounds a query by O(min(n, (k + 1) log n))
+/// expected time rather than the O(log n + k) of a static centered interval tree.
+pub struct IntervalMap<K: Ord + Clone, V> {
+    /// Every value is stored next to a copy of the end of its interval, which is what the
+    /// summaries are computed from.
+    inner: AugmentedTreeMap<Range<K>, (K, V), MaxEnd, ByBounds>,
+}
+
+/// Keeps the largest interval end, `None` for no intervals at all.
+struct MaxEnd;
+
+impl<K: Ord + Clone, V> Monoid<(K, V)> for MaxEnd {
+    type Summary = Option<K>;
+
+    fn identity(&self) -> Option<K> {
+        None
+    }
+
+    fn lift(&self, (end, _): &(K, V)) -> Option<K> {
+        Some(end.clone())
+    }
+
+    fn combine(&self, a: &Option<K>, b: &Option<K>) -> Option<K> {
+        a.as_ref().max(b.as_ref()).cloned()
+    }
+}
+
+/// Orders intervals by `(start, end)`.
+struct ByBounds;
+
+impl<K: Ord> Comparator<Range<K>> for ByBounds {
+    fn compare(&self, a: &Range<K>, b: &Range<K>) -> Ordering {
+        (&a.start, &a.end).cmp(&(&b.start, &b.end))
+    }
+}
+
+impl<K: Ord + Clone + Debug, V: Debug> Debug for IntervalMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord + Clone, V> IntervalMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            inner: AugmentedTreeMap::with_comparator(MaxEnd, ByBounds),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns an iterator over the intervals and their values, sorted by `(start, end)`.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.inner.iter(),
+        }
+    }
+
+    /// Inserts the interval, returning the previous value if the exact same interval was
+    /// already present.
+    ///
+    /// Panics if the interval is empty.
+    pub fn insert(&mut self, interval: Range<K>, value: V) -> Option<V> {
+        assert!(interval.start < interval.end, "interval is empty");
+        let end = interval.end.clone();
+        self.inner.insert(interval, (end, value)).map(|(_, v)| v)
+    }
+
+    pub fn remove(&mut self, interval: &Range<K>) -> Option<V> {
+        self.inner.remove(interval).map(|(_, v)| v)
+    }
+
+    pub fn get(&self, interval: &Range<K>) -> Option<&V> {
+        self.inner.get(interval).map(|(_, v)| v)
+    }
+
+    /// Returns the intervals containing `point`, sorted by `(start, end)`.
+    ///
+    /// Takes O(min(n, (k + 1) log n)) expected time for the k intervals reported.
+    pub fn stab<'a>(&'a self, point: &'a K) -> Overlaps<'a, K, V> {
+        Overlaps::new(self.inner.root(), point, point, true)
+    }
+
+    /// Returns the intervals that share at least one point with `range`, sorted by
+    /// `(start, end)`. An empty or inverted `range` has no points and overlaps nothing.
+    ///
+    /// Takes O(min(n, (k + 1) log n)) expected time for the k intervals reported.
+    pub fn overlapping<'a>(&'a self, range: &'a Range<K>) -> Overlaps<'a, K, V> {
+        let root = self.inner.root().filter(|_| range.start < range.end);
+        Overlaps::new(root, &range.start, &range.end, false)
+    }
+}
+
+impl<K: Ord + Clone, V> Default for IntervalMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone, V> FromIterator<(Range<K>, V)> for IntervalMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (Range<K>, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        for (interval, value) in iter {
+            map.insert(interval, value);
+        }
+        map
+    }
+}
+
+impl<'a, K: Ord + Clone, V> IntoIterator for &'a IntervalMap<K, V> {
+    type Item = (&'a Range<K>, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+type Node<K, V> = augmented::Node<Range<K>, (K, V), Option<K>>;
+
+/// Iterator over all intervals of an [`IntervalMap`], sorted by `(start, end)`.
+pub struct Iter<'a, K, V> {
+    inner: augmented::Iter<'a, Range<K>, (K, V), Option<K>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(interval, (_, v))| (interval, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+/// Iterator over the intervals of an [`IntervalMap`] that overlap a query, sorted by
+/// `(start, end)`.
+pub struct Overlaps<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+    /// Reported intervals have to end after `low`.
+    low: &'a K,
+    /// Reported intervals have to start before `high`, or at it if `inclusive` is set.
+    high: &'a K,
+    inclusive: bool,
+}
+
+impl<'a, K: Ord, V> Overlaps<'a, K, V> {
+    fn new(root: Option<&'a Node<K, V>>, low: &'a K, high: &'a K, inclusive: bool) -> Self {
+        let mut iter = Self {
+            stack: Vec::new(),
+            low,
+            high,
+            inclusive,
+        };
+        iter.push_left(root);
+        iter
+    }
+
+    /// Descends to the leftmost node, skipping subtrees that end too early to overlap.
+    fn push_left(&mut self, mut node: Option<&'a Node<K, V>>) {
+        while let Some(n) = node.filter(|n| n.summary().as_ref() > Some(self.low)) {
+            self.stack.push(n);
+            node = n.left();
+        }
+    }
+
+    fn starts_in_time(&self, start: &K) -> bool {
+        if self.inclusive {
+            start <= self.high
+        } else {
+            start < self.high
+        }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for Overlaps<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            let interval = node.key();
+            if !self.starts_in_time(&interval.start) {
+                // Everything left to visit starts even later.
+                self.stack.clear();
+                return None;
+            }
+            self.push_left(node.right());
+            if interval.end > *self.low {
+                return Some((interval, &node.value().1));
+            }
+        }
+        None
+    }
+}
+
+impl<'a, K: Ord, V> FusedIterator for Overlaps<'a, K, V> {}
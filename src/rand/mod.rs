@@ -1,9 +1,27 @@
 mod alias;
+pub mod arena;
+pub mod augmented;
 mod event_emitter;
-mod rtree;
-mod rset;
+pub mod interval;
+pub mod persistent;
+pub mod rmultiset;
+pub mod rset;
+pub mod rtree;
+#[cfg(feature = "stream")]
+mod stream;
+pub mod transaction;
+pub mod treap_vec;
 
 pub use alias::Alias;
-pub use event_emitter::EventEmmiter;
-pub use rtree::RTreeMap;
+pub use arena::ArenaTreeMap;
+pub use augmented::AugmentedTreeMap;
+pub use event_emitter::{Emitter, EventEmmiter, Leaf};
+pub use interval::IntervalMap;
+pub use persistent::PersistentTreeMap;
+pub use rmultiset::RMultiSet;
 pub use rset::RSet;
+pub use rtree::RTreeMap;
+#[cfg(feature = "stream")]
+pub use stream::{EventStream, Rate, Timer};
+pub use transaction::{Savepoint, Transaction};
+pub use treap_vec::TreapVec;
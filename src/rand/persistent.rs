@@ -0,0 +1,313 @@
+use crate::compare::{Comparator, Natural};
+use std::{borrow::Borrow, cmp::Ordering, fmt::Debug, iter::FusedIterator, sync::Arc};
+
+/// An immutable ordered map backed by a treap whose nodes are shared between versions.
+///
+/// Cloning a map takes O(1) time. Updates copy only the nodes on the path to the changed entry,
+/// O(log n) expected, and return a new version while every older version stays valid. Nodes
+/// are reference counted with [`Arc`], so versions can be handed to other threads.
+pub struct PersistentTreeMap<K, V, C = Natural> {
+    root: Link<K, V>,
+    len: usize,
+    cmp: C,
+}
+
+impl<K: Debug, V: Debug, C> Debug for PersistentTreeMap<K, V, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V> PersistentTreeMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<K, V, C> PersistentTreeMap<K, V, C> {
+    /// Creates an empty map that orders its keys by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            root: None,
+            len: 0,
+            cmp,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            match self.cmp.compare(n.key.borrow(), key) {
+                Ordering::Greater => node = n.left.as_deref(),
+                Ordering::Equal => return Some(&n.value),
+                Ordering::Less => node = n.right.as_deref(),
+            }
+        }
+        None
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns an iterator over the entries, sorted by key.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            len: self.len,
+        };
+        iter.push_left(self.root.as_deref());
+        iter
+    }
+
+    /// Returns a new version of the map without the entry for `key`. Returns a plain clone if
+    /// the key is missing.
+    pub fn remove<Q>(&self, key: &Q) -> Self
+    where
+        K: Borrow<Q> + Clone,
+        V: Clone,
+        Q: ?Sized,
+        C: Comparator<Q> + Clone,
+    {
+        match Node::remove(&self.root, key, &self.cmp) {
+            Some(root) => Self {
+                root,
+                len: self.len - 1,
+                cmp: self.cmp.clone(),
+            },
+            None => self.clone(),
+        }
+    }
+}
+
+impl<K: Clone, V: Clone, C: Comparator<K> + Clone> PersistentTreeMap<K, V, C> {
+    /// Returns a new version of the map that maps `key` to `value`, replacing the value of an
+    /// existing entry.
+    pub fn insert(&self, key: K, value: V) -> Self {
+        let node = Node {
+            key,
+            value,
+            priority: rand::random(),
+            left: None,
+            right: None,
+        };
+        let (root, replaced) = Node::insert(&self.root, node, &self.cmp);
+        Self {
+            root: Some(root),
+            len: self.len + usize::from(!replaced),
+            cmp: self.cmp.clone(),
+        }
+    }
+}
+
+impl<K, V, C: Clone> Clone for PersistentTreeMap<K, V, C> {
+    /// Shares all nodes with `self`, so this only bumps the reference count of the root.
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            len: self.len,
+            cmp: self.cmp.clone(),
+        }
+    }
+}
+
+impl<K, V, C: Default> Default for PersistentTreeMap<K, V, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+impl<K: Clone, V: Clone, C: Comparator<K> + Clone + Default> FromIterator<(K, V)>
+    for PersistentTreeMap<K, V, C>
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::default(), |map, (k, v)| map.insert(k, v))
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a PersistentTreeMap<K, V, C> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// In order iterator over the entries of a [`PersistentTreeMap`].
+///
+/// The nodes have no `parent` pointers since they can be shared by several parents, so the
+/// iterator keeps the path to the next entry on a stack.
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+    len: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(&mut self, mut node: Option<&'a Node<K, V>>) {
+        while let Some(n) = node {
+            self.stack.push(n);
+            node = n.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.len -= 1;
+        self.push_left(node.right.as_deref());
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> Clone for Iter<'a, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            stack: self.stack.clone(),
+            len: self.len,
+        }
+    }
+}
+
+type Link<K, V> = Option<Arc<Node<K, V>>>;
+
+pub struct Node<K, V> {
+    key: K,
+    value: V,
+    priority: usize,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+impl<K: Clone, V: Clone> Node<K, V> {
+    /// Copies the node with new children, sharing everything else.
+    fn with_children(&self, left: Link<K, V>, right: Link<K, V>) -> Arc<Self> {
+        Arc::new(Self {
+            key: self.key.clone(),
+            value: self.value.clone(),
+            priority: self.priority,
+            left,
+            right,
+        })
+    }
+
+    /// Inserts `new` below `link` in a single descent that copies the path to it. Returns the
+    /// new subtree and whether `new` replaced an entry with the same key.
+    ///
+    /// An entry with the same key above the place of `new` keeps its priority and only takes
+    /// the key and value of `new`. Otherwise `new` takes the place of the first node on its
+    /// search path with a lower priority, splitting that subtree.
+    fn insert<C: Comparator<K>>(link: &Link<K, V>, mut new: Self, cmp: &C) -> (Arc<Self>, bool) {
+        match link {
+            Some(n) if n.priority >= new.priority => match cmp.compare(&new.key, &n.key) {
+                Ordering::Equal => {
+                    let node = Self {
+                        priority: n.priority,
+                        left: n.left.clone(),
+                        right: n.right.clone(),
+                        ..new
+                    };
+                    (Arc::new(node), true)
+                }
+                Ordering::Less => {
+                    let (left, replaced) = Self::insert(&n.left, new, cmp);
+                    (n.with_children(Some(left), n.right.clone()), replaced)
+                }
+                Ordering::Greater => {
+                    let (right, replaced) = Self::insert(&n.right, new, cmp);
+                    (n.with_children(n.left.clone(), Some(right)), replaced)
+                }
+            },
+            _ => {
+                let (left, right, replaced) = Self::split(link, &new.key, cmp);
+                (new.left, new.right) = (left, right);
+                (Arc::new(new), replaced)
+            }
+        }
+    }
+
+    /// Splits the subtree into the keys less than and greater than `key`, leaving out the entry
+    /// with an equal key and returning whether there was one.
+    fn split<C: Comparator<K>>(
+        link: &Link<K, V>,
+        key: &K,
+        cmp: &C,
+    ) -> (Link<K, V>, Link<K, V>, bool) {
+        let Some(n) = link else {
+            return (None, None, false);
+        };
+        match cmp.compare(&n.key, key) {
+            // The subtrees of the equal entry lie entirely on either side of `key`.
+            Ordering::Equal => (n.left.clone(), n.right.clone(), true),
+            Ordering::Less => {
+                let (l, r, found) = Self::split(&n.right, key, cmp);
+                (Some(n.with_children(n.left.clone(), l)), r, found)
+            }
+            Ordering::Greater => {
+                let (l, r, found) = Self::split(&n.left, key, cmp);
+                (l, Some(n.with_children(r, n.right.clone())), found)
+            }
+        }
+    }
+
+    /// Joins two subtrees where every key of `a` is less than every key of `b`.
+    fn merge(a: &Link<K, V>, b: &Link<K, V>) -> Link<K, V> {
+        match (a, b) {
+            (None, b) => b.clone(),
+            (a, None) => a.clone(),
+            (Some(x), Some(y)) => Some(if x.priority > y.priority {
+                x.with_children(x.left.clone(), Self::merge(&x.right, b))
+            } else {
+                y.with_children(Self::merge(a, &y.left), y.right.clone())
+            }),
+        }
+    }
+
+    /// Returns the new subtree without `key`, or `None` if the key is missing.
+    fn remove<Q, C>(link: &Link<K, V>, key: &Q, cmp: &C) -> Option<Link<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let n = link.as_ref()?;
+        Some(match cmp.compare(n.key.borrow(), key) {
+            Ordering::Equal => Self::merge(&n.left, &n.right),
+            Ordering::Greater => {
+                Some(n.with_children(Self::remove(&n.left, key, cmp)?, n.right.clone()))
+            }
+            Ordering::Less => {
+                Some(n.with_children(n.left.clone(), Self::remove(&n.right, key, cmp)?))
+            }
+        })
+    }
+}
@@ -0,0 +1,388 @@
+use super::{augmented, AugmentedTreeMap};
+use crate::{
+    compare::{Comparator, Natural},
+    monoid::Monoid,
+};
+use std::{borrow::Borrow, fmt::Debug, iter::FusedIterator, ops::Bound};
+
+/// An ordered multiset that stores a count per distinct key.
+///
+/// Backed by an [`AugmentedTreeMap`] that sums up the counts of every subtree, so the order
+/// statistics [`RMultiSet::nth`] and [`RMultiSet::rank`] count every copy of a key and still
+/// take O(log n) expected time. A plain [`RTreeMap`](super::RTreeMap) only caches the number
+/// of distinct keys below a node, which would leave them at O(n).
+pub struct RMultiSet<K, C = Natural> {
+    inner: AugmentedTreeMap<K, usize, Counts, C>,
+}
+
+/// Adds up the counts like [`Sum`](crate::monoid::Sum), but panics instead of wrapping when
+/// the total no longer fits in a `usize`.
+#[derive(Clone)]
+struct Counts;
+
+impl Monoid<usize> for Counts {
+    type Summary = usize;
+
+    fn identity(&self) -> usize {
+        0
+    }
+
+    fn lift(&self, count: &usize) -> usize {
+        *count
+    }
+
+    fn combine(&self, a: &usize, b: &usize) -> usize {
+        a.checked_add(*b).expect("multiset count overflow")
+    }
+}
+
+impl<K: Debug, C> Debug for RMultiSet<K, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<K> RMultiSet<K> {
+    pub fn new() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<K, C> RMultiSet<K, C> {
+    /// Creates an empty multiset that orders its keys by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            inner: AugmentedTreeMap::with_comparator(Counts, cmp),
+        }
+    }
+
+    /// Returns the number of keys in the multiset, counting every copy.
+    pub fn len(&self) -> usize {
+        self.inner.total()
+    }
+
+    /// Returns the number of distinct keys in the multiset.
+    pub fn distinct_len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns how many copies of `key` the multiset holds.
+    pub fn count<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.get(key).copied().unwrap_or(0)
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.contains(key)
+    }
+
+    /// Removes a single copy of `key`, returning whether there was one.
+    pub fn remove_one<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        match self.count(key) {
+            0 => false,
+            1 => self.inner.remove(key).is_some(),
+            _ => self.inner.update(key, |count| *count -= 1),
+        }
+    }
+
+    /// Removes every copy of `key`, returning how many there were.
+    pub fn remove_all<Q>(&mut self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.remove(key).unwrap_or(0)
+    }
+
+    /// Returns the `k`-th smallest key counting every copy, starting from zero.
+    pub fn nth(&self, k: usize) -> Option<&K> {
+        self.inner.search_prefix(|&count| count > k).map(|(k, _)| k)
+    }
+
+    /// Returns the number of keys in the multiset that are smaller than `key`, counting every
+    /// copy.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner
+            .aggregate((Bound::Unbounded, Bound::Excluded(key)))
+    }
+
+    /// Returns an iterator over the keys in ascending order, repeating every key as often as
+    /// it is in the multiset.
+    pub fn iter(&self) -> Iter<'_, K> {
+        Iter {
+            inner: self.inner.iter(),
+            current: None,
+            len: self.len(),
+        }
+    }
+
+    /// Returns an iterator over the distinct keys in ascending order with their counts.
+    pub fn counts(&self) -> impl Iterator<Item = (&K, usize)> + '_ {
+        self.inner.iter().map(|(k, c)| (k, *c))
+    }
+
+    pub fn first(&self) -> Option<&K> {
+        self.inner.first_key_value().map(|(k, _)| k)
+    }
+
+    pub fn last(&self) -> Option<&K> {
+        self.inner.last_key_value().map(|(k, _)| k)
+    }
+
+    /// Returns the largest key less than or equal to `key`.
+    pub fn floor<Q>(&self, key: &Q) -> Option<&K>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.floor(key).map(|(k, _)| k)
+    }
+
+    /// Returns the smallest key greater than or equal to `key`.
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<&K>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.ceiling(key).map(|(k, _)| k)
+    }
+
+    /// Returns the largest key strictly less than `key`.
+    pub fn predecessor<Q>(&self, key: &Q) -> Option<&K>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.predecessor(key).map(|(k, _)| k)
+    }
+
+    /// Returns the smallest key strictly greater than `key`.
+    pub fn successor<Q>(&self, key: &Q) -> Option<&K>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.successor(key).map(|(k, _)| k)
+    }
+
+    /// Splits the multiset in two at `key`, returning every copy of the keys after and
+    /// including `key`.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q> + Clone,
+    {
+        Self {
+            inner: self.inner.split_off(key),
+        }
+    }
+
+    /// Returns whether every key occurs in `other` at least as often as in `self`.
+    pub fn is_subset(&self, other: &Self) -> bool
+    where
+        C: Comparator<K>,
+    {
+        self.distinct_len() <= other.distinct_len()
+            && self.counts().all(|(key, count)| count <= other.count(key))
+    }
+
+    pub fn is_superset(&self, other: &Self) -> bool
+    where
+        C: Comparator<K>,
+    {
+        other.is_subset(self)
+    }
+}
+
+// Multiset algebra on the counts of the keys. Every operation merges the underlying treaps
+// like `RTreeMap::union`, in O(m log(n / m)) expected time for m <= n distinct keys.
+impl<K, C: Comparator<K>> RMultiSet<K, C> {
+    /// Moves every copy of the keys of `other` into the multiset, adding up the counts.
+    ///
+    /// Panics if the multisets hold more than `usize::MAX` keys together.
+    pub fn append(&mut self, other: &mut Self) {
+        // The counts of a key are part of the totals, so they cannot overflow either.
+        self.len()
+            .checked_add(other.len())
+            .expect("multiset count overflow");
+        self.inner
+            .merge_with(&mut other.inner, (true, true), |_, count, theirs| {
+                *count += theirs;
+                true
+            });
+    }
+
+    /// Joins two multisets in O(log n) expected time.
+    ///
+    /// Panics if the keys of `left` are not all smaller than the keys of `right`.
+    pub fn join(left: Self, right: Self) -> Self {
+        Self {
+            inner: AugmentedTreeMap::join(left.inner, right.inner),
+        }
+    }
+
+    /// Keeps every key with the larger of its counts in both multisets.
+    ///
+    /// Panics if the result would hold more than `usize::MAX` keys.
+    pub fn into_union(mut self, mut other: Self) -> Self {
+        self.inner
+            .merge_with(&mut other.inner, (true, true), |_, count, theirs| {
+                *count = (*count).max(theirs);
+                true
+            });
+        self
+    }
+
+    /// Keeps every key with the sum of its counts in both multisets.
+    ///
+    /// Panics if the result would hold more than `usize::MAX` keys.
+    pub fn into_sum(mut self, mut other: Self) -> Self {
+        self.append(&mut other);
+        self
+    }
+
+    /// Keeps the keys present in both multisets with the smaller of their counts.
+    pub fn into_intersection(mut self, mut other: Self) -> Self {
+        self.inner
+            .merge_with(&mut other.inner, (false, false), |_, count, theirs| {
+                *count = (*count).min(theirs);
+                true
+            });
+        self
+    }
+
+    /// Keeps the copies of `self` that `other` does not cancel out, key by key.
+    pub fn into_difference(mut self, mut other: Self) -> Self {
+        self.inner
+            .merge_with(&mut other.inner, (true, false), |_, count, theirs| {
+                *count = count.saturating_sub(theirs);
+                *count > 0
+            });
+        self
+    }
+
+    /// Keeps every key with the difference of its counts in both multisets.
+    ///
+    /// Panics if the result would hold more than `usize::MAX` keys.
+    pub fn into_symmetric_difference(mut self, mut other: Self) -> Self {
+        self.inner
+            .merge_with(&mut other.inner, (true, true), |_, count, theirs| {
+                *count = count.abs_diff(theirs);
+                *count > 0
+            });
+        self
+    }
+}
+
+impl<K, C: Comparator<K>> RMultiSet<K, C> {
+    /// Adds a copy of `key`, returning how many copies there are now.
+    pub fn insert(&mut self, key: K) -> usize {
+        self.insert_many(key, 1)
+    }
+
+    /// Adds `n` copies of `key`, returning how many copies there are now.
+    ///
+    /// Panics if the multiset would hold more than `usize::MAX` keys.
+    pub fn insert_many(&mut self, key: K, n: usize) -> usize {
+        // The count of `key` is part of the total, so it cannot overflow either.
+        self.len().checked_add(n).expect("multiset count overflow");
+        let count = self.count(&key) + n;
+        if count > 0 {
+            self.inner.insert(key, count);
+        }
+        count
+    }
+}
+
+impl<K, C: Default> Default for RMultiSet<K, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+impl<K, C: Comparator<K> + Default> FromIterator<K> for RMultiSet<K, C> {
+    fn from_iter<I: IntoIterator<Item = K>>(iter: I) -> Self {
+        let mut set = Self::default();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<K, C: Comparator<K>> Extend<K> for RMultiSet<K, C> {
+    fn extend<I: IntoIterator<Item = K>>(&mut self, iter: I) {
+        for key in iter {
+            self.insert(key);
+        }
+    }
+}
+
+impl<'a, K, C> IntoIterator for &'a RMultiSet<K, C> {
+    type Item = &'a K;
+    type IntoIter = Iter<'a, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// In order iterator over the keys of a [`RMultiSet`], repeating every key as often as it is
+/// in the multiset.
+pub struct Iter<'a, K> {
+    inner: augmented::Iter<'a, K, usize, usize>,
+    /// The key being repeated and how many copies of it are left.
+    current: Option<(&'a K, usize)>,
+    len: usize,
+}
+
+impl<'a, K> Iterator for Iter<'a, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match &mut self.current {
+                Some((key, left)) if *left > 0 => {
+                    *left -= 1;
+                    self.len -= 1;
+                    return Some(*key);
+                }
+                _ => self.current = Some(self.inner.next().map(|(k, c)| (k, *c))?),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, K> ExactSizeIterator for Iter<'a, K> {}
+impl<'a, K> FusedIterator for Iter<'a, K> {}
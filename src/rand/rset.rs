@@ -1,21 +1,514 @@
-use super::RTreeMap;
+use super::{rtree, RTreeMap};
+use crate::compare::{Comparator, Natural};
+use rand::Rng;
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    iter::{FusedIterator, Peekable},
+    ops::RangeBounds,
+};
 
-pub struct RSet<K: PartialOrd> {
-    inner: RTreeMap<K, ()>,
+/// An ordered set backed by a [`RTreeMap`] with `()` values.
+pub struct RSet<K, C = Natural> {
+    inner: RTreeMap<K, (), C>,
 }
 
-impl<K: PartialOrd> RSet<K> {
+impl<K> RSet<K> {
     pub fn new() -> Self {
         Self {
             inner: RTreeMap::new(),
         }
     }
+}
+
+impl<K, C> RSet<K, C> {
+    /// Creates an empty set that orders its keys by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            inner: RTreeMap::with_comparator(cmp),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Removes `key`, returning whether it was present.
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.remove(key).is_some()
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.contains(key)
+    }
+
+    /// Returns a double ended iterator over the keys in ascending order.
+    pub fn iter(&self) -> Iter<'_, K> {
+        Iter {
+            inner: self.inner.keys(),
+        }
+    }
+
+    /// Returns a double ended iterator over the keys that lie in `range`, in ascending order.
+    ///
+    /// Panics if the start of the range is greater than its end.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        R: RangeBounds<Q>,
+    {
+        Range {
+            inner: self.inner.range(range),
+        }
+    }
+
+    pub fn first(&self) -> Option<&K> {
+        self.inner.first_key_value().map(|(k, _)| k)
+    }
+
+    pub fn last(&self) -> Option<&K> {
+        self.inner.last_key_value().map(|(k, _)| k)
+    }
+
+    pub fn pop_first(&mut self) -> Option<K> {
+        self.inner.pop_first().map(|(k, _)| k)
+    }
+
+    pub fn pop_last(&mut self) -> Option<K> {
+        self.inner.pop_last().map(|(k, _)| k)
+    }
+
+    /// Returns the largest key less than or equal to `key`.
+    pub fn floor<Q>(&self, key: &Q) -> Option<&K>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.floor(key).map(|(k, _)| k)
+    }
+
+    /// Returns the smallest key greater than or equal to `key`.
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<&K>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.ceiling(key).map(|(k, _)| k)
+    }
+
+    /// Returns the largest key strictly less than `key`.
+    pub fn predecessor<Q>(&self, key: &Q) -> Option<&K>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.predecessor(key).map(|(k, _)| k)
+    }
+
+    /// Returns the smallest key strictly greater than `key`.
+    pub fn successor<Q>(&self, key: &Q) -> Option<&K>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.successor(key).map(|(k, _)| k)
+    }
+
+    /// Returns a key chosen uniformly at random in O(log n) expected time.
+    ///
+    /// Sampling proportional to weights needs the per-subtree weight sums of an
+    /// [`AugmentedTreeMap`](super::AugmentedTreeMap), see
+    /// [`AugmentedTreeMap::choose_weighted`](super::AugmentedTreeMap::choose_weighted).
+    pub fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&K> {
+        self.inner.choose(rng).map(|(k, _)| k)
+    }
+
+    /// Returns the `k`-th smallest key, counting from zero.
+    pub fn nth(&self, k: usize) -> Option<&K> {
+        self.inner.nth(k).map(|(k, _)| k)
+    }
+
+    /// Returns the number of keys in the set that are smaller than `key`.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.inner.rank(key)
+    }
+
+    /// Splits off the keys greater than or equal to `key` into a new set.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q> + Clone,
+    {
+        Self {
+            inner: self.inner.split_off(key),
+        }
+    }
+}
+
+impl<K, C: Comparator<K>> RSet<K, C> {
+    /// Adds `key`, returning whether it was newly inserted.
+    pub fn insert(&mut self, key: K) -> bool {
+        self.inner.insert(key, ()).is_none()
+    }
+
+    /// Moves all keys of `other` into `self`, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        self.inner.append(&mut other.inner);
+    }
+
+    /// Returns the keys in `self` or `other` in ascending order, merging both sets lazily.
+    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, K, C> {
+        Union(self.merge(other))
+    }
+
+    /// Returns the keys in both `self` and `other` in ascending order, merging both sets lazily.
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> Intersection<'a, K, C> {
+        Intersection(self.merge(other))
+    }
+
+    /// Returns the keys in `self` but not in `other` in ascending order, merging both sets
+    /// lazily.
+    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, K, C> {
+        Difference(self.merge(other))
+    }
+
+    /// Returns the keys in exactly one of `self` and `other` in ascending order, merging both
+    /// sets lazily.
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Self) -> SymmetricDifference<'a, K, C> {
+        SymmetricDifference(self.merge(other))
+    }
+
+    fn merge<'a>(&'a self, other: &'a Self) -> MergeIter<'a, K, C> {
+        MergeIter {
+            a: self.iter().peekable(),
+            b: other.iter().peekable(),
+            cmp: self.inner.comparator(),
+        }
+    }
+
+    /// Builds the union by splitting and joining the treaps, which takes O(m log(n / m))
+    /// expected time for sets of sizes m <= n.
+    pub fn into_union(self, other: Self) -> Self {
+        Self {
+            inner: self.inner.union(other.inner),
+        }
+    }
 
-    pub fn insert(&mut self, key: K) {
-        self.inner.insert(key, ());
+    /// Builds the intersection by splitting and joining the treaps, which takes
+    /// O(m log(n / m)) expected time for sets of sizes m <= n.
+    pub fn into_intersection(self, other: Self) -> Self {
+        Self {
+            inner: self.inner.intersection(other.inner),
+        }
+    }
+
+    /// Builds the difference by splitting and joining the treaps, which takes O(m log(n / m))
+    /// expected time for sets of sizes m <= n.
+    pub fn into_difference(self, other: Self) -> Self {
+        Self {
+            inner: self.inner.difference(other.inner),
+        }
     }
 
-    pub fn remove(&mut self, key: K) {
-        self.inner.remove(key);
+    /// Builds the symmetric difference by splitting and joining the treaps, which takes
+    /// O(m log(n / m)) expected time for sets of sizes m <= n.
+    pub fn into_symmetric_difference(self, other: Self) -> Self {
+        Self {
+            inner: self.inner.symmetric_difference(other.inner),
+        }
+    }
+
+    /// Returns whether every key of `self` is in `other`, looking each of them up in O(log n).
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.len() <= other.len() && self.iter().all(|k| other.contains(k))
+    }
+
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns whether the sets share no key, looking up the keys of the smaller set in the
+    /// larger one.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        small.iter().all(|k| !large.contains(k))
+    }
+}
+
+impl<K: Debug, C> Debug for RSet<K, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Clone, C: Comparator<K> + Clone> Clone for RSet<K, C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<K: PartialEq, C> PartialEq for RSet<K, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl<K: Eq, C> Eq for RSet<K, C> {}
+
+impl<K: PartialOrd, C> PartialOrd for RSet<K, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
     }
 }
+
+impl<K: Ord, C> Ord for RSet<K, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Hash, C> Hash for RSet<K, C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner.hash(state);
+    }
+}
+
+impl<K, C: Default> Default for RSet<K, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+impl<K, C: Comparator<K> + Default> FromIterator<K> for RSet<K, C> {
+    /// Collects the keys in O(n) expected time if they arrive sorted.
+    fn from_iter<I: IntoIterator<Item = K>>(iter: I) -> Self {
+        Self {
+            inner: iter.into_iter().map(|k| (k, ())).collect(),
+        }
+    }
+}
+
+impl<K, C: Comparator<K> + Clone> Extend<K> for RSet<K, C> {
+    fn extend<I: IntoIterator<Item = K>>(&mut self, iter: I) {
+        self.inner.extend(iter.into_iter().map(|k| (k, ())));
+    }
+}
+
+impl<'a, K: Copy + 'a, C: Comparator<K> + Clone> Extend<&'a K> for RSet<K, C> {
+    fn extend<I: IntoIterator<Item = &'a K>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+impl<'a, K, C> IntoIterator for &'a RSet<K, C> {
+    type Item = &'a K;
+    type IntoIter = Iter<'a, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, C> IntoIterator for RSet<K, C> {
+    type Item = K;
+    type IntoIter = IntoIter<K, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.inner.into_iter(),
+        }
+    }
+}
+
+/// In order iterator over the keys of a [`RSet`].
+pub struct Iter<'a, K> {
+    inner: rtree::Keys<'a, K, ()>,
+}
+
+impl<'a, K> Iterator for Iter<'a, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K> DoubleEndedIterator for Iter<'a, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K> ExactSizeIterator for Iter<'a, K> {}
+impl<'a, K> FusedIterator for Iter<'a, K> {}
+
+/// In order iterator over the keys of a [`RSet`] that lie in a range.
+pub struct Range<'a, K> {
+    inner: rtree::Range<'a, K, ()>,
+}
+
+impl<'a, K> Iterator for Range<'a, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+}
+
+impl<'a, K> DoubleEndedIterator for Range<'a, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<'a, K> FusedIterator for Range<'a, K> {}
+
+/// Owning in order iterator over the keys of a [`RSet`].
+pub struct IntoIter<K, C = Natural> {
+    inner: rtree::IntoIter<K, (), C>,
+}
+
+impl<K, C> Iterator for IntoIter<K, C> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, C> DoubleEndedIterator for IntoIter<K, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<K, C> ExactSizeIterator for IntoIter<K, C> {}
+impl<K, C> FusedIterator for IntoIter<K, C> {}
+
+/// Walks two sets side by side, pairing up equal keys.
+struct MergeIter<'a, K, C> {
+    a: Peekable<Iter<'a, K>>,
+    b: Peekable<Iter<'a, K>>,
+    cmp: &'a C,
+}
+
+impl<'a, K, C: Comparator<K>> Iterator for MergeIter<'a, K, C> {
+    type Item = (Option<&'a K>, Option<&'a K>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let order = match (self.a.peek(), self.b.peek()) {
+            (None, None) => return None,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(a), Some(b)) => self.cmp.compare(a, b),
+        };
+        Some(match order {
+            Ordering::Less => (self.a.next(), None),
+            Ordering::Equal => (self.a.next(), self.b.next()),
+            Ordering::Greater => (None, self.b.next()),
+        })
+    }
+}
+
+/// Lazy union of two [`RSet`]s, see [`RSet::union`].
+pub struct Union<'a, K, C = Natural>(MergeIter<'a, K, C>);
+
+impl<'a, K, C: Comparator<K>> Iterator for Union<'a, K, C> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().and_then(|(a, b)| a.or(b))
+    }
+}
+
+impl<'a, K, C: Comparator<K>> FusedIterator for Union<'a, K, C> {}
+
+/// Lazy intersection of two [`RSet`]s, see [`RSet::intersection`].
+pub struct Intersection<'a, K, C = Natural>(MergeIter<'a, K, C>);
+
+impl<'a, K, C: Comparator<K>> Iterator for Intersection<'a, K, C> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find_map(|pair| match pair {
+            (Some(a), Some(_)) => Some(a),
+            _ => None,
+        })
+    }
+}
+
+impl<'a, K, C: Comparator<K>> FusedIterator for Intersection<'a, K, C> {}
+
+/// Lazy difference of two [`RSet`]s, see [`RSet::difference`].
+pub struct Difference<'a, K, C = Natural>(MergeIter<'a, K, C>);
+
+impl<'a, K, C: Comparator<K>> Iterator for Difference<'a, K, C> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find_map(|pair| match pair {
+            (Some(a), None) => Some(a),
+            _ => None,
+        })
+    }
+}
+
+impl<'a, K, C: Comparator<K>> FusedIterator for Difference<'a, K, C> {}
+
+/// Lazy symmetric difference of two [`RSet`]s, see [`RSet::symmetric_difference`].
+pub struct SymmetricDifference<'a, K, C = Natural>(MergeIter<'a, K, C>);
+
+impl<'a, K, C: Comparator<K>> Iterator for SymmetricDifference<'a, K, C> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find_map(|pair| match pair {
+            (Some(k), None) | (None, Some(k)) => Some(k),
+            _ => None,
+        })
+    }
+}
+
+impl<'a, K, C: Comparator<K>> FusedIterator for SymmetricDifference<'a, K, C> {}
@@ -1,116 +1,1673 @@
-use rand::{rngs::ThreadRng, thread_rng, Rng};
-use std::{cmp::Ordering, fmt::Debug, ptr::NonNull};
+use crate::{
+    compare::{Comparator, Natural},
+    invariant::InvariantError,
+};
+use rand::{thread_rng, Rng};
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    marker::PhantomData,
+    ops::{Bound, Index, RangeBounds},
+    ptr::NonNull,
+};
 
-pub struct RTreeMap<K: PartialOrd, V> {
-    rng: ThreadRng,
+/// An ordered map backed by a treap, ordering its keys by the comparator `C`.
+///
+/// Priorities are drawn from the thread local generator on insertion instead of one owned by
+/// the map, so the map can be sent to other threads.
+pub struct RTreeMap<K, V, C = Natural> {
     root: Link<K, V>,
+    len: usize,
+    cmp: C,
 }
 
-impl<K: PartialOrd + Debug, V: Debug> Debug for RTreeMap<K, V> {
+impl<K: Debug, V: Debug, C> Debug for RTreeMap<K, V, C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!(
-            "{:?}",
-            &self.root.map(|p| unsafe { p.as_ref() })
-        ))
+        f.debug_map().entries(self.iter()).finish()
     }
 }
 
-impl<K: PartialOrd, V> RTreeMap<K, V> {
+// The map owns its nodes exclusively, the raw pointers never escape into shared state.
+unsafe impl<K: Send, V: Send, C: Send> Send for RTreeMap<K, V, C> {}
+unsafe impl<K: Sync, V: Sync, C: Sync> Sync for RTreeMap<K, V, C> {}
+
+impl<K, V> RTreeMap<K, V> {
     pub fn new() -> Self {
+        Self::with_comparator(Natural)
+    }
+
+    /// Builds a map from entries sorted by key in O(n) expected time, see
+    /// [`RTreeMap::from_sorted_iter_with_comparator`].
+    pub fn from_sorted_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self
+    where
+        K: Ord,
+    {
+        Self::from_sorted_iter_with_comparator(iter, Natural)
+    }
+}
+
+impl<K, V, C> RTreeMap<K, V, C> {
+    /// Creates an empty map that orders its keys by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self {
         Self {
-            rng: thread_rng(),
             root: None,
+            len: 0,
+            cmp,
+        }
+    }
+
+    pub fn comparator(&self) -> &C {
+        &self.cmp
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Looks for the node holding the key for which `cmp` returns `Equal`, or else returns the
+    /// node and the side below which it would have to be inserted.
+    ///
+    /// `cmp` orders the key of a node relative to the key that is searched for.
+    fn search<F: Fn(&K) -> Ordering>(&self, cmp: F) -> Result<NonNull<Node<K, V>>, Slot<K, V>> {
+        let mut slot = None;
+        let mut node = self.root;
+        while let Some(n) = node {
+            let n_ref = unsafe { n.as_ref() };
+            match cmp(&n_ref.key) {
+                Ordering::Equal => return Ok(n),
+                Ordering::Greater => {
+                    slot = Some((n, Direction::Left));
+                    node = n_ref.left;
+                }
+                Ordering::Less => {
+                    slot = Some((n, Direction::Right));
+                    node = n_ref.right;
+                }
+            }
+        }
+        Err(slot)
+    }
+
+    fn find<Q>(&self, key: &Q) -> Link<K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.search(|k| self.cmp.compare(k.borrow(), key)).ok()
+    }
+
+    /// Links a new node into `slot` and rotates it up until the heap order holds again.
+    unsafe fn insert_at(&mut self, slot: Slot<K, V>, key: K, value: V) -> NonNull<Node<K, V>> {
+        let priority = thread_rng().gen();
+        let parent = slot.as_ref().map(|(p, _)| *p);
+        let node = NonNull::from(Box::leak(Node::new_with_parent(
+            key, value, priority, parent,
+        )));
+        match slot {
+            Some((mut p, Direction::Left)) => p.as_mut().left = Some(node),
+            Some((mut p, Direction::Right)) => p.as_mut().right = Some(node),
+            None => self.root = Some(node),
+        }
+        Node::grow_path(parent);
+        Node::fix(parent);
+        // It might happen that we are no longer referencing the root node.
+        if let Some(p) = self.root.and_then(|r| r.as_ref().parent) {
+            self.root = Some(p);
+        }
+        self.len += 1;
+        node
+    }
+
+    /// Rotates `node` down until it is a leaf, then unlinks and frees it.
+    unsafe fn remove_node(&mut self, mut node: NonNull<Node<K, V>>) -> (K, V) {
+        while let Some(d) = node.as_ref().get_highest_prio_child() {
+            match d {
+                Direction::Left => node.as_mut().rotate_right(),
+                Direction::Right => node.as_mut().rotate_left(),
+            }
+            // The first rotation of the root node makes its child the new root.
+            if self.root == Some(node) {
+                self.root = node.as_ref().parent;
+            }
+        }
+        match node.as_ref().parent {
+            Some(mut parent) => {
+                Node::shrink_path(Some(parent));
+                if parent.as_ref().left == Some(node) {
+                    parent.as_mut().left = None;
+                } else {
+                    parent.as_mut().right = None;
+                }
+            }
+            None => self.root = None,
+        }
+        self.len -= 1;
+        let node = Box::from_raw(node.as_ptr());
+        (node.key, node.value)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes the entry for `key`, returning the stored key along with the value.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let node = self.find(key)?;
+        Some(unsafe { self.remove_node(node) })
+    }
+
+    pub fn contains<Q>(&self, needle: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.find(needle).is_some()
+    }
+
+    pub fn get<Q>(&self, needle: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.find(needle).map(|n| unsafe { &(*n.as_ptr()).value })
+    }
+
+    pub fn get_mut<Q>(&mut self, needle: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.find(needle)
+            .map(|n| unsafe { &mut (*n.as_ptr()).value })
+    }
+
+    /// Returns an iterator over the entries of the map, sorted by key.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            front: self.root.map(|r| unsafe { Node::leftmost(r) }),
+            back: self.root.map(|r| unsafe { Node::rightmost(r) }),
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the entries of the map, sorted by key, with mutable references
+    /// to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            front: self.root.map(|r| unsafe { Node::leftmost(r) }),
+            back: self.root.map(|r| unsafe { Node::rightmost(r) }),
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    fn key_value<'a>(node: Link<K, V>) -> Option<(&'a K, &'a V)> {
+        node.map(|n| unsafe {
+            let n = &*n.as_ptr();
+            (&n.key, &n.value)
+        })
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        Self::key_value(unsafe { Node::first(self.root) })
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        Self::key_value(unsafe { Node::last(self.root) })
+    }
+
+    /// Removes and returns the entry with the smallest key.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let node = unsafe { Node::first(self.root) }?;
+        Some(unsafe { self.remove_node(node) })
+    }
+
+    /// Removes and returns the entry with the largest key.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let node = unsafe { Node::last(self.root) }?;
+        Some(unsafe { self.remove_node(node) })
+    }
+
+    /// Returns the entry with the largest key less than or equal to `key`.
+    pub fn floor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        Self::key_value(unsafe { Node::upper_bound(self.root, Bound::Included(key), &self.cmp) })
+    }
+
+    /// Returns the entry with the smallest key greater than or equal to `key`.
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        Self::key_value(unsafe { Node::lower_bound(self.root, Bound::Included(key), &self.cmp) })
+    }
+
+    /// Returns the entry with the largest key strictly less than `key`.
+    pub fn predecessor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        Self::key_value(unsafe { Node::upper_bound(self.root, Bound::Excluded(key), &self.cmp) })
+    }
+
+    /// Returns the entry with the smallest key strictly greater than `key`.
+    pub fn successor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        Self::key_value(unsafe { Node::lower_bound(self.root, Bound::Excluded(key), &self.cmp) })
+    }
+
+    /// The node after `node` in key order, where `None` stands for the ghost position of a
+    /// cursor.
+    unsafe fn next_of(&self, node: Link<K, V>) -> Link<K, V> {
+        match node {
+            Some(n) => Node::successor(n),
+            None => Node::first(self.root),
+        }
+    }
+
+    unsafe fn prev_of(&self, node: Link<K, V>) -> Link<K, V> {
+        match node {
+            Some(n) => Node::predecessor(n),
+            None => Node::last(self.root),
+        }
+    }
+
+    /// Returns an entry chosen uniformly at random, locating it through the subtree sizes in
+    /// O(log n) expected time.
+    ///
+    /// The nodes only cache subtree sizes, so sampling proportional to a weight needs an
+    /// [`AugmentedTreeMap`](super::AugmentedTreeMap) whose monoid sums the weights, and its
+    /// [`choose_weighted`](super::AugmentedTreeMap::choose_weighted).
+    pub fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<(&K, &V)> {
+        if self.is_empty() {
+            return None;
+        }
+        self.nth(rng.gen_range(0..self.len))
+    }
+
+    /// Returns the entry with the `k`-th smallest key, counting from zero.
+    pub fn nth(&self, mut k: usize) -> Option<(&K, &V)> {
+        let mut node = self.root;
+        while let Some(n) = node.map(|n| unsafe { n.as_ref() }) {
+            let left = unsafe { Node::size_of(n.left) };
+            match k.cmp(&left) {
+                Ordering::Less => node = n.left,
+                Ordering::Equal => return Some((&n.key, &n.value)),
+                Ordering::Greater => {
+                    k -= left + 1;
+                    node = n.right;
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the number of keys in the map that are smaller than `key`.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut rank = 0;
+        let mut node = self.root;
+        while let Some(n) = node.map(|n| unsafe { n.as_ref() }) {
+            if self.cmp.compare(key, n.key.borrow()).is_le() {
+                node = n.left;
+            } else {
+                rank += unsafe { Node::size_of(n.left) } + 1;
+                node = n.right;
+            }
+        }
+        rank
+    }
+
+    /// Splits the map in two at `key`, returning everything after and including `key`.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q> + Clone,
+    {
+        let (left, right) = unsafe { Node::split(self.root.take(), key, &self.cmp) };
+        self.set_root(left);
+        let mut other = Self::with_comparator(self.cmp.clone());
+        other.set_root(right);
+        other
+    }
+
+    /// Removes all entries, returning them as an owning iterator sorted by key.
+    ///
+    /// The map is emptied right away, so entries that are not consumed are dropped along with
+    /// the iterator.
+    pub fn drain(&mut self) -> Drain<K, V> {
+        self.len = 0;
+        Drain::new(self.root.take())
+    }
+
+    /// Removes the entries whose keys lie in `range`, returning them as an owning iterator
+    /// sorted by key.
+    ///
+    /// Cuts the range out with two splits and joins the rest back together, so it takes
+    /// O(log n) expected time plus the time to consume the iterator.
+    ///
+    /// Panics if the start of the range is greater than its end.
+    pub fn drain_range<Q, R>(&mut self, range: R) -> Drain<K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        R: RangeBounds<Q>,
+    {
+        check_range(&self.cmp, &range);
+        let cmp = &self.cmp;
+        let (left, rest) = unsafe {
+            Node::split_by(self.root.take(), |k| match range.start_bound() {
+                Bound::Included(s) => cmp.compare(k.borrow(), s).is_lt(),
+                Bound::Excluded(s) => cmp.compare(k.borrow(), s).is_le(),
+                Bound::Unbounded => false,
+            })
+        };
+        let (middle, right) = unsafe {
+            Node::split_by(rest, |k| match range.end_bound() {
+                Bound::Included(e) => cmp.compare(k.borrow(), e).is_le(),
+                Bound::Excluded(e) => cmp.compare(k.borrow(), e).is_lt(),
+                Bound::Unbounded => true,
+            })
+        };
+        self.set_root(unsafe { Node::merge(left, right) });
+        Drain::new(middle)
+    }
+
+    /// Keeps only the entries for which `f` returns `true`, visiting them in ascending order.
+    ///
+    /// Takes O(n) expected time, see [`RTreeMap::extract_if`].
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Returns an iterator that visits the entries in ascending order and removes those for
+    /// which `pred` returns `true`, yielding them.
+    ///
+    /// The kept entries are relinked into a fresh treap along its right spine as the iterator
+    /// goes, so a full pass takes O(n) expected time. Entries that were not visited when the
+    /// iterator is dropped stay in the map.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, C, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let rest = self.root.take();
+        let remaining = std::mem::take(&mut self.len);
+        ExtractIf {
+            next: unsafe { Node::first(rest) },
+            map: self,
+            rest,
+            remaining,
+            spine: Vec::new(),
+            pred,
+        }
+    }
+
+    /// Makes `root` the root of the map, updating the length accordingly.
+    fn set_root(&mut self, root: Link<K, V>) {
+        unsafe { Node::set_parent(root, None) };
+        self.root = root;
+        self.len = unsafe { Node::size_of(root) };
+    }
+
+    /// Returns a double ended iterator over the entries whose keys lie in `range`, sorted by key.
+    ///
+    /// Panics if the start of the range is greater than its end.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        R: RangeBounds<Q>,
+    {
+        Range {
+            ends: self.range_ends(range),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a double ended iterator over the entries whose keys lie in `range`, sorted by key,
+    /// with mutable references to the values.
+    ///
+    /// Panics if the start of the range is greater than its end.
+    pub fn range_mut<Q, R>(&mut self, range: R) -> RangeMut<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        R: RangeBounds<Q>,
+    {
+        RangeMut {
+            ends: self.range_ends(range),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a cursor pointing at the first entry whose key lies above `bound`, or at the
+    /// "ghost" position past the last entry if there is none.
+    pub fn lower_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, C>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        Cursor {
+            current: unsafe { Node::lower_bound(self.root, bound, &self.cmp) },
+            map: self,
+        }
+    }
+
+    /// Like [`RTreeMap::lower_bound`], but the cursor can modify the map.
+    pub fn lower_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V, C>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        CursorMut {
+            current: unsafe { Node::lower_bound(self.root, bound, &self.cmp) },
+            map: self,
+        }
+    }
+
+    /// Finds the first and the last node inside of `range`.
+    fn range_ends<Q, R>(&self, range: R) -> RangeEnds<K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        R: RangeBounds<Q>,
+    {
+        check_range(&self.cmp, &range);
+        let front = unsafe { Node::lower_bound(self.root, range.start_bound(), &self.cmp) };
+        let back = unsafe { Node::upper_bound(self.root, range.end_bound(), &self.cmp) };
+        match (front, back) {
+            (Some(f), Some(b))
+                if unsafe {
+                    self.cmp
+                        .compare(f.as_ref().key.borrow(), b.as_ref().key.borrow())
+                        .is_le()
+                } =>
+            {
+                RangeEnds { front, back }
+            }
+            _ => RangeEnds {
+                front: None,
+                back: None,
+            },
+        }
+    }
+}
+
+impl<K, V, C: Comparator<K>> RTreeMap<K, V, C> {
+    /// Walks the whole tree and checks key order, heap order on the priorities, `parent`
+    /// pointers, subtree sizes and the length of the map, reporting the first violation.
+    pub fn validate(&self) -> Result<(), InvariantError<'_, K>> {
+        let mut count = 0;
+        // Every node together with its expected parent and the keys it has to lie between.
+        let mut stack = Vec::from_iter(self.root.map(|r| (r, None, None, None)));
+        while let Some((node, parent, low, high)) = stack.pop() {
+            let n = unsafe { node.as_ref() };
+            let key = &n.key;
+            count += 1;
+            if n.parent != parent {
+                return Err(InvariantError::ParentLink { key });
+            }
+            if low.is_some_and(|low| self.cmp.compare(low, key).is_ge())
+                || high.is_some_and(|high| self.cmp.compare(key, high).is_ge())
+            {
+                return Err(InvariantError::KeyOrder { key });
+            }
+            if parent.is_some_and(|p| unsafe { p.as_ref().priority } < n.priority) {
+                return Err(InvariantError::HeapOrder { key });
+            }
+            let expected = 1 + unsafe { Node::size_of(n.left) + Node::size_of(n.right) };
+            if n.size != expected {
+                return Err(InvariantError::Size {
+                    key,
+                    expected,
+                    found: n.size,
+                });
+            }
+            if let Some(r) = n.right {
+                stack.push((r, Some(node), Some(key), high));
+            }
+            if let Some(l) = n.left {
+                stack.push((l, Some(node), low, Some(key)));
+            }
+        }
+        if count != self.len {
+            return Err(InvariantError::Len {
+                expected: count,
+                found: self.len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Builds a map ordered by `cmp` from entries sorted by key.
+    ///
+    /// As long as the keys are ascending the treap is built bottom up along its right spine,
+    /// which takes O(n) expected time. Later duplicates of a key replace its value, and entries
+    /// that are out of order are inserted one by one instead.
+    pub fn from_sorted_iter_with_comparator<I: IntoIterator<Item = (K, V)>>(
+        iter: I,
+        cmp: C,
+    ) -> Self {
+        let mut map = Self::with_comparator(cmp);
+        let mut rng = thread_rng();
+        let mut iter = iter.into_iter();
+        // The right spine of the tree built so far, from the root down to the largest node.
+        let mut spine: Vec<NonNull<Node<K, V>>> = Vec::new();
+        let mut rest = None;
+        for (key, value) in iter.by_ref() {
+            if let Some(mut last) = spine.last().copied() {
+                match map.cmp.compare(unsafe { &last.as_ref().key }, &key) {
+                    Ordering::Less => {}
+                    Ordering::Equal => {
+                        unsafe { last.as_mut().value = value };
+                        continue;
+                    }
+                    Ordering::Greater => {
+                        rest = Some((key, value));
+                        break;
+                    }
+                }
+            }
+            let node = Node::new_with_parent(key, value, rng.gen(), None);
+            unsafe { Node::push_spine(&mut spine, NonNull::from(Box::leak(node))) };
+            map.len += 1;
+        }
+        map.root = unsafe { Node::finish_spine(&mut spine) };
+        for (key, value) in rest.into_iter().chain(iter) {
+            map.insert(key, value);
+        }
+        map
+    }
+
+    /// Inserts the entry, returning the previous value if the key was already present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut o) => Some(o.insert(value)),
+            Entry::Vacant(v) => {
+                v.insert(value);
+                None
+            }
+        }
+    }
+
+    /// Returns the entry for `key`, which allows to inspect and update it with a single
+    /// descent.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, C> {
+        match self.search(|k| self.cmp.compare(k, &key)) {
+            Ok(node) => Entry::Occupied(OccupiedEntry { map: self, node }),
+            Err(slot) => Entry::Vacant(VacantEntry {
+                map: self,
+                key,
+                slot,
+            }),
+        }
+    }
+
+    /// Moves all entries of `other` into the map, leaving `other` empty. Values of `other` win
+    /// over the values of the map for keys present in both.
+    ///
+    /// Runs in O(log n) expected time if the keys of one map are all smaller than the keys of
+    /// the other and falls back to [`RTreeMap::union`] otherwise.
+    pub fn append(&mut self, other: &mut Self) {
+        let (a, b) = (self.root.take(), other.root.take());
+        other.len = 0;
+        let before = |l: Link<K, V>, r: Link<K, V>| unsafe {
+            match (Node::last(l), Node::first(r)) {
+                (Some(max), Some(min)) => self
+                    .cmp
+                    .compare(&max.as_ref().key, &min.as_ref().key)
+                    .is_lt(),
+                _ => true,
+            }
+        };
+        let root = unsafe {
+            if before(a, b) {
+                Node::merge(a, b)
+            } else if before(b, a) {
+                Node::merge(b, a)
+            } else {
+                Node::merge_sets(a, b, SetOp::UNION, &self.cmp)
+            }
+        };
+        self.set_root(root);
+    }
+
+    /// Joins two maps in O(log n) expected time.
+    ///
+    /// Panics if the keys of `left` are not all smaller than the keys of `right`.
+    pub fn join(mut left: Self, mut right: Self) -> Self {
+        let (a, b) = (left.root.take(), right.root.take());
+        unsafe {
+            if let (Some(max), Some(min)) = (Node::last(a), Node::first(b)) {
+                if left
+                    .cmp
+                    .compare(&max.as_ref().key, &min.as_ref().key)
+                    .is_ge()
+                {
+                    // Put the trees back so that they are freed.
+                    left.root = a;
+                    right.root = b;
+                    panic!("left map overlaps the right map");
+                }
+            }
+            left.set_root(Node::merge(a, b));
+        }
+        left
+    }
+
+    /// Merges two possibly overlapping maps, keeping the values of `other` for keys present in
+    /// both. Takes O(m log(n / m)) expected time for maps of sizes m <= n.
+    pub fn union(mut self, mut other: Self) -> Self {
+        let (a, b) = (self.root.take(), other.root.take());
+        let root = unsafe { Node::merge_sets(a, b, SetOp::UNION, &self.cmp) };
+        self.set_root(root);
+        self
+    }
+
+    /// Keeps the entries of `self` whose keys are also present in `other`. Takes
+    /// O(m log(n / m)) expected time for maps of sizes m <= n.
+    pub fn intersection(mut self, mut other: Self) -> Self {
+        let (a, b) = (self.root.take(), other.root.take());
+        let root = unsafe { Node::merge_sets(a, b, SetOp::INTERSECTION, &self.cmp) };
+        self.set_root(root);
+        self
+    }
+
+    /// Keeps the entries of `self` whose keys are missing from `other`. Takes
+    /// O(m log(n / m)) expected time for maps of sizes m <= n.
+    pub fn difference(mut self, mut other: Self) -> Self {
+        let (a, b) = (self.root.take(), other.root.take());
+        let root = unsafe { Node::merge_sets(a, b, SetOp::DIFFERENCE, &self.cmp) };
+        self.set_root(root);
+        self
+    }
+
+    /// Keeps the entries whose keys are present in exactly one of the maps. Takes
+    /// O(m log(n / m)) expected time for maps of sizes m <= n.
+    pub fn symmetric_difference(mut self, mut other: Self) -> Self {
+        let (a, b) = (self.root.take(), other.root.take());
+        let root = unsafe { Node::merge_sets(a, b, SetOp::SYMMETRIC_DIFFERENCE, &self.cmp) };
+        self.set_root(root);
+        self
+    }
+}
+
+impl<K, V, C: Comparator<K> + Default> FromIterator<(K, V)> for RTreeMap<K, V, C> {
+    /// Collects the entries in O(n) expected time if they arrive sorted by key.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_sorted_iter_with_comparator(iter, C::default())
+    }
+}
+
+impl<K, V, C: Comparator<K> + Clone> Extend<(K, V)> for RTreeMap<K, V, C> {
+    /// Builds a treap from the new entries and merges it into the map, which only takes O(n)
+    /// expected time if they arrive sorted and after the keys already in the map.
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let mut other = Self::from_sorted_iter_with_comparator(iter, self.cmp.clone());
+        self.append(&mut other);
+    }
+}
+
+impl<'a, K: Copy + 'a, V: Copy + 'a, C: Comparator<K> + Clone> Extend<(&'a K, &'a V)>
+    for RTreeMap<K, V, C>
+{
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(k, v)| (*k, *v)));
+    }
+}
+
+impl<K: Clone, V: Clone, C: Comparator<K> + Clone> Clone for RTreeMap<K, V, C> {
+    /// Rebuilds the treap from the sorted entries in O(n) expected time.
+    fn clone(&self) -> Self {
+        let entries = self.iter().map(|(k, v)| (k.clone(), v.clone()));
+        Self::from_sorted_iter_with_comparator(entries, self.cmp.clone())
+    }
+}
+
+impl<K: PartialEq, V: PartialEq, C> PartialEq for RTreeMap<K, V, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq, C> Eq for RTreeMap<K, V, C> {}
+
+impl<K: PartialOrd, V: PartialOrd, C> PartialOrd for RTreeMap<K, V, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, V: Ord, C> Ord for RTreeMap<K, V, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Hash, V: Hash, C> Hash for RTreeMap<K, V, C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        for entry in self {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<K, Q, V, C> Index<&Q> for RTreeMap<K, V, C>
+where
+    K: Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+{
+    type Output = V;
+
+    /// Panics if the key is not present in the map.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, C: Default> Default for RTreeMap<K, V, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+impl<K, V, C> Drop for RTreeMap<K, V, C> {
+    fn drop(&mut self) {
+        unsafe { Node::drop_tree(self.root.take()) };
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a RTreeMap<K, V, C> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a mut RTreeMap<K, V, C> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, C> IntoIterator for RTreeMap<K, V, C> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            front: self.root.map(|r| unsafe { Node::leftmost(r) }),
+            back: self.root.map(|r| unsafe { Node::rightmost(r) }),
+            map: self,
+        }
+    }
+}
+
+/// In order iterator over the entries of a [`RTreeMap`].
+///
+/// Walks the tree along the `parent` pointers of the nodes, so it does not need a stack.
+pub struct Iter<'a, K, V> {
+    front: Link<K, V>,
+    back: Link<K, V>,
+    len: usize,
+    _marker: PhantomData<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let node = unsafe { self.front?.as_ref() };
+        self.front = unsafe { Node::successor(node.into()) };
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let node = unsafe { self.back?.as_ref() };
+        self.back = unsafe { Node::predecessor(node.into()) };
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Send for Iter<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Sync for Iter<'a, K, V> {}
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> Clone for Iter<'a, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            front: self.front,
+            back: self.back,
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// In order iterator over the entries of a [`RTreeMap`] with mutable references to the values.
+pub struct IterMut<'a, K, V> {
+    front: Link<K, V>,
+    back: Link<K, V>,
+    len: usize,
+    _marker: PhantomData<&'a mut Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let node = unsafe { self.front?.as_mut() };
+        self.front = unsafe { Node::successor(node.into()) };
+        Some((&node.key, &mut node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let node = unsafe { self.back?.as_mut() };
+        self.back = unsafe { Node::predecessor(node.into()) };
+        Some((&node.key, &mut node.value))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Send> Send for IterMut<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Sync for IterMut<'a, K, V> {}
+impl<'a, K, V> FusedIterator for IterMut<'a, K, V> {}
+
+/// Owning in order iterator over the entries of a [`RTreeMap`].
+///
+/// Every step unlinks the smallest (or largest) remaining node, which never has more than one
+/// child, so the remaining nodes always form a valid tree that the map frees on drop.
+pub struct IntoIter<K, V, C = Natural> {
+    map: RTreeMap<K, V, C>,
+    front: Link<K, V>,
+    back: Link<K, V>,
+}
+
+impl<K, V, C> IntoIter<K, V, C> {
+    /// Replaces `node`, which has at most one child, by that child and frees it.
+    unsafe fn unlink(&mut self, node: NonNull<Node<K, V>>) -> (K, V) {
+        let node = Box::from_raw(node.as_ptr());
+        let child = node.left.or(node.right);
+        if let Some(mut c) = child {
+            c.as_mut().parent = node.parent;
+        }
+        match node.parent {
+            Some(mut p) => {
+                if p.as_ref().left == Some(NonNull::from(&*node)) {
+                    p.as_mut().left = child;
+                } else {
+                    p.as_mut().right = child;
+                }
+            }
+            None => self.map.root = child,
+        }
+        self.map.len -= 1;
+        (node.key, node.value)
+    }
+}
+
+impl<K, V, C> Iterator for IntoIter<K, V, C> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.map.len == 0 {
+            return None;
+        }
+        let node = self.front?;
+        unsafe {
+            self.front = Node::successor(node);
+            Some(self.unlink(node))
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.map.len, Some(self.map.len))
+    }
+}
+
+impl<K, V, C> DoubleEndedIterator for IntoIter<K, V, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.map.len == 0 {
+            return None;
+        }
+        let node = self.back?;
+        unsafe {
+            self.back = Node::predecessor(node);
+            Some(self.unlink(node))
+        }
+    }
+}
+
+impl<K, V, C> ExactSizeIterator for IntoIter<K, V, C> {}
+unsafe impl<K: Send, V: Send, C: Send> Send for IntoIter<K, V, C> {}
+unsafe impl<K: Sync, V: Sync, C: Sync> Sync for IntoIter<K, V, C> {}
+impl<K, V, C> FusedIterator for IntoIter<K, V, C> {}
+
+/// Owning iterator over the entries removed by [`RTreeMap::drain`] and
+/// [`RTreeMap::drain_range`].
+pub struct Drain<K, V> {
+    inner: IntoIter<K, V>,
+}
+
+impl<K, V> Drain<K, V> {
+    /// Takes ownership of the detached tree under `root`.
+    fn new(root: Link<K, V>) -> Self {
+        let mut map = RTreeMap::new();
+        map.set_root(root);
+        Self {
+            inner: map.into_iter(),
+        }
+    }
+}
+
+impl<K, V> Iterator for Drain<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Drain<K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Drain<K, V> {}
+impl<K, V> FusedIterator for Drain<K, V> {}
+
+/// Iterator that removes the entries of a [`RTreeMap`] matching a predicate, see
+/// [`RTreeMap::extract_if`].
+///
+/// While it is alive the map is split into the kept entries, built up along the right spine,
+/// and the entries not visited yet. Dropping the iterator joins them back together.
+pub struct ExtractIf<'a, K, V, C, F> {
+    map: &'a mut RTreeMap<K, V, C>,
+    /// The entries that were not visited yet.
+    rest: Link<K, V>,
+    /// The smallest node of `rest`.
+    next: Link<K, V>,
+    remaining: usize,
+    /// The right spine of the tree of kept entries, from the root down.
+    spine: Vec<NonNull<Node<K, V>>>,
+    pred: F,
+}
+
+impl<'a, K, V, C, F> Iterator for ExtractIf<'a, K, V, C, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(mut node) = self.next {
+            unsafe {
+                let n = node.as_mut();
+                // The node stays in `rest` until `pred` returns, so a panic does not lose it.
+                let extract = (self.pred)(&n.key, &mut n.value);
+                // The smallest node has no left child, so its right child simply takes its
+                // place. The sizes along the left spine of `rest` go stale, which only matters
+                // once the iterator is dropped.
+                let right = n.right.take();
+                self.next = right.map(|r| Node::leftmost(r)).or(n.parent);
+                Node::attach(&mut self.rest, n.parent, Direction::Left, right);
+                self.remaining -= 1;
+                if extract {
+                    let node = Box::from_raw(node.as_ptr());
+                    return Some((node.key, node.value));
+                }
+                Node::push_spine(&mut self.spine, node);
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V, C, F> FusedIterator for ExtractIf<'a, K, V, C, F> where F: FnMut(&K, &mut V) -> bool {}
+
+unsafe impl<'a, K: Send, V: Send, C: Send, F: Send> Send for ExtractIf<'a, K, V, C, F> {}
+unsafe impl<'a, K: Sync, V: Sync, C: Sync, F: Sync> Sync for ExtractIf<'a, K, V, C, F> {}
+
+impl<'a, K, V, C, F> Drop for ExtractIf<'a, K, V, C, F> {
+    fn drop(&mut self) {
+        unsafe {
+            Node::update_path(Node::first(self.rest));
+            let kept = Node::finish_spine(&mut self.spine);
+            self.map.set_root(Node::merge(kept, self.rest.take()));
+        }
+    }
+}
+
+/// The first and last node of a range that has not been yielded yet, both `None` once the range
+/// is exhausted.
+struct RangeEnds<K, V> {
+    front: Link<K, V>,
+    back: Link<K, V>,
+}
+
+impl<K, V> RangeEnds<K, V> {
+    fn next(&mut self) -> Link<K, V> {
+        let node = self.front?;
+        if self.front == self.back {
+            self.back = None;
+            self.front = None;
+        } else {
+            self.front = unsafe { Node::successor(node) };
+        }
+        Some(node)
+    }
+
+    fn next_back(&mut self) -> Link<K, V> {
+        let node = self.back?;
+        if self.front == self.back {
+            self.back = None;
+            self.front = None;
+        } else {
+            self.back = unsafe { Node::predecessor(node) };
+        }
+        Some(node)
+    }
+}
+
+/// Iterator over a sub range of the entries of a [`RTreeMap`].
+pub struct Range<'a, K, V> {
+    ends: RangeEnds<K, V>,
+    _marker: PhantomData<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = unsafe { self.ends.next()?.as_ref() };
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = unsafe { self.ends.next_back()?.as_ref() };
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<'a, K, V> FusedIterator for Range<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Send for Range<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Sync for Range<'a, K, V> {}
+
+impl<'a, K, V> Clone for Range<'a, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            ends: RangeEnds {
+                front: self.ends.front,
+                back: self.ends.back,
+            },
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Iterator over a sub range of the entries of a [`RTreeMap`] with mutable references to the
+/// values.
+pub struct RangeMut<'a, K, V> {
+    ends: RangeEnds<K, V>,
+    _marker: PhantomData<&'a mut Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = unsafe { self.ends.next()?.as_mut() };
+        Some((&node.key, &mut node.value))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for RangeMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = unsafe { self.ends.next_back()?.as_mut() };
+        Some((&node.key, &mut node.value))
+    }
+}
+
+impl<'a, K, V> FusedIterator for RangeMut<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Send> Send for RangeMut<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Sync for RangeMut<'a, K, V> {}
+
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {}
+impl<'a, K, V> FusedIterator for Keys<'a, K, V> {}
+
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {}
+impl<'a, K, V> FusedIterator for Values<'a, K, V> {}
+
+pub struct ValuesMut<'a, K, V> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for ValuesMut<'a, K, V> {}
+impl<'a, K, V> FusedIterator for ValuesMut<'a, K, V> {}
+
+/// A view into a single entry of a [`RTreeMap`], which may either be occupied or vacant.
+pub enum Entry<'a, K, V, C = Natural> {
+    Occupied(OccupiedEntry<'a, K, V, C>),
+    Vacant(VacantEntry<'a, K, V, C>),
+}
+
+impl<'a, K, V, C> Entry<'a, K, V, C> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(o) => o.key(),
+            Entry::Vacant(v) => v.key(),
         }
     }
 
-    pub fn insert(&mut self, key: K, value: V) {
-        let priority = self.rng.gen();
-        if let Some(root) = self.root.as_ref() {
-            unsafe { Node::insert(root.clone(), key, value, priority) };
-            // It might happen that we are no longer referencing the root node.
-            if unsafe { self.root.unwrap().as_ref().parent.is_some() } {
-                self.root = unsafe { self.root.unwrap().as_ref().parent };
-            }
-        } else {
-            self.root = NonNull::new(Box::into_raw(Node::new(key, value, priority)));
+    /// Inserts `default` if the entry is vacant and returns a mutable reference to the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(o) => o.into_mut(),
+            Entry::Vacant(v) => v.insert(default),
         }
     }
 
-    pub fn remove(&mut self, key: K) -> Option<V> {
-        if let Some(mut root) = self.root.take() {
-            // we need to delete the root node so we have to replace it now to keep a reference to
-            // the tree.
-            unsafe {
-                if root.as_mut().key == key {
-                    match root.as_ref().get_highest_prio_child() {
-                        Some(d) => match d {
-                            Direction::Left => root.as_mut().rotate_right(),
-                            Direction::Right => root.as_mut().rotate_left(),
-                        },
-                        None => {
-                            let h = Box::from_raw(root.as_ptr());
-                            return Some(h.value);
-                        }
-                    }
-                    self.root = root.as_ref().parent;
-                } else {
-                    // Since we took the root node we will have to put it back in case it is not
-                    // the match.
-                    self.root = Some(root);
-                }
-                return Node::remove(root, key);
-            }
-        } else {
-            None
+    /// Inserts the result of `default` if the entry is vacant and returns a mutable reference
+    /// to the value.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(o) => o.into_mut(),
+            Entry::Vacant(v) => v.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` on the value if the entry is occupied.
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(o) = &mut self {
+            f(o.get_mut());
         }
+        self
+    }
+}
+
+pub struct OccupiedEntry<'a, K, V, C = Natural> {
+    map: &'a mut RTreeMap<K, V, C>,
+    node: NonNull<Node<K, V>>,
+}
+
+unsafe impl<'a, K: Send, V: Send, C: Send> Send for OccupiedEntry<'a, K, V, C> {}
+unsafe impl<'a, K: Sync, V: Sync, C: Sync> Sync for OccupiedEntry<'a, K, V, C> {}
+
+impl<'a, K, V, C> OccupiedEntry<'a, K, V, C> {
+    pub fn key(&self) -> &K {
+        unsafe { &self.node.as_ref().key }
+    }
+
+    pub fn get(&self) -> &V {
+        unsafe { &self.node.as_ref().value }
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        unsafe { &mut self.node.as_mut().value }
     }
 
-    pub fn contains(&self, needle: K) -> bool {
-        self.root
-            .map(|r| unsafe { r.as_ref().get(needle).is_some() })
-            .unwrap_or(false)
+    pub fn into_mut(mut self) -> &'a mut V {
+        unsafe { &mut self.node.as_mut().value }
     }
 
-    pub fn get(&self, needle: K) -> Option<&V> {
-        self.root
-            .map(|r| unsafe { r.as_ref().get(needle) })
-            .unwrap_or_default()
+    /// Replaces the value of the entry, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
     }
 
-    pub fn get_mut(&self, needle: K) -> Option<&mut V> {
-        self.root
-            .map(|mut r| unsafe { r.as_mut().get_mut(needle) })
-            .unwrap_or_default()
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the entry from the map, returning its key and value.
+    pub fn remove_entry(self) -> (K, V) {
+        unsafe { self.map.remove_node(self.node) }
     }
 }
 
-impl<K: PartialOrd, V> Drop for RTreeMap<K, V> {
-    fn drop(&mut self) {
-        if let Some(root) = self.root {
-            let root = unsafe { Box::from_raw(root.as_ptr()) };
-            root.drop_children();
+pub struct VacantEntry<'a, K, V, C = Natural> {
+    map: &'a mut RTreeMap<K, V, C>,
+    key: K,
+    slot: Slot<K, V>,
+}
+
+unsafe impl<'a, K: Send, V: Send, C: Send> Send for VacantEntry<'a, K, V, C> {}
+unsafe impl<'a, K: Sync, V: Sync, C: Sync> Sync for VacantEntry<'a, K, V, C> {}
+
+impl<'a, K, V, C> VacantEntry<'a, K, V, C> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the entry with `value` and returns a mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        unsafe {
+            let mut node = self.map.insert_at(self.slot, self.key, value);
+            &mut node.as_mut().value
+        }
+    }
+}
+
+/// A cursor over the entries of a [`RTreeMap`], sitting either on an entry or on the "ghost"
+/// position between the last and the first entry.
+///
+/// Moves along the `parent` pointers of the nodes, so a step takes O(1) amortized time.
+pub struct Cursor<'a, K, V, C = Natural> {
+    map: &'a RTreeMap<K, V, C>,
+    current: Link<K, V>,
+}
+
+unsafe impl<'a, K: Sync, V: Sync, C: Sync> Send for Cursor<'a, K, V, C> {}
+unsafe impl<'a, K: Sync, V: Sync, C: Sync> Sync for Cursor<'a, K, V, C> {}
+
+impl<'a, K, V, C> Cursor<'a, K, V, C> {
+    /// Moves to the next entry, or from the last entry onto the ghost position and from there
+    /// to the first entry.
+    pub fn move_next(&mut self) {
+        self.current = unsafe { self.map.next_of(self.current) };
+    }
+
+    /// Moves to the previous entry, or from the first entry onto the ghost position and from
+    /// there to the last entry.
+    pub fn move_prev(&mut self) {
+        self.current = unsafe { self.map.prev_of(self.current) };
+    }
+
+    /// Returns the entry under the cursor, `None` on the ghost position.
+    pub fn peek(&self) -> Option<(&'a K, &'a V)> {
+        RTreeMap::<K, V, C>::key_value(self.current)
+    }
+
+    pub fn peek_next(&self) -> Option<(&'a K, &'a V)> {
+        RTreeMap::<K, V, C>::key_value(unsafe { self.map.next_of(self.current) })
+    }
+
+    pub fn peek_prev(&self) -> Option<(&'a K, &'a V)> {
+        RTreeMap::<K, V, C>::key_value(unsafe { self.map.prev_of(self.current) })
+    }
+}
+
+impl<'a, K, V, C> Clone for Cursor<'a, K, V, C> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map,
+            current: self.current,
+        }
+    }
+}
+
+/// A cursor over the entries of a [`RTreeMap`] that can also insert and remove entries around
+/// its position.
+pub struct CursorMut<'a, K, V, C = Natural> {
+    map: &'a mut RTreeMap<K, V, C>,
+    current: Link<K, V>,
+}
+
+unsafe impl<'a, K: Send, V: Send, C: Send> Send for CursorMut<'a, K, V, C> {}
+unsafe impl<'a, K: Sync, V: Sync, C: Sync> Sync for CursorMut<'a, K, V, C> {}
+
+impl<'a, K, V, C> CursorMut<'a, K, V, C> {
+    /// Moves to the next entry, or from the last entry onto the ghost position and from there
+    /// to the first entry.
+    pub fn move_next(&mut self) {
+        self.current = unsafe { self.map.next_of(self.current) };
+    }
+
+    /// Moves to the previous entry, or from the first entry onto the ghost position and from
+    /// there to the last entry.
+    pub fn move_prev(&mut self) {
+        self.current = unsafe { self.map.prev_of(self.current) };
+    }
+
+    /// Returns the entry under the cursor, `None` on the ghost position.
+    pub fn peek(&self) -> Option<(&K, &V)> {
+        RTreeMap::<K, V, C>::key_value(self.current)
+    }
+
+    pub fn peek_mut(&mut self) -> Option<(&K, &mut V)> {
+        self.current.map(|n| unsafe {
+            let n = &mut *n.as_ptr();
+            (&n.key, &mut n.value)
+        })
+    }
+
+    pub fn peek_next(&self) -> Option<(&K, &V)> {
+        RTreeMap::<K, V, C>::key_value(unsafe { self.map.next_of(self.current) })
+    }
+
+    pub fn peek_prev(&self) -> Option<(&K, &V)> {
+        RTreeMap::<K, V, C>::key_value(unsafe { self.map.prev_of(self.current) })
+    }
+
+    /// Returns a read only cursor at the same position.
+    pub fn as_cursor(&self) -> Cursor<'_, K, V, C> {
+        Cursor {
+            map: self.map,
+            current: self.current,
+        }
+    }
+
+    /// Inserts the entry right before the cursor, which stays on its entry. On the ghost
+    /// position the entry is appended after the last one.
+    ///
+    /// Panics if `key` does not lie strictly between the keys of the previous entry and the
+    /// entry under the cursor.
+    pub fn insert_before(&mut self, key: K, value: V)
+    where
+        C: Comparator<K>,
+    {
+        unsafe {
+            let prev = self.map.prev_of(self.current);
+            let in_order = prev.is_none_or(|p| self.map.cmp.compare(&p.as_ref().key, &key).is_lt())
+                && self
+                    .current
+                    .is_none_or(|c| self.map.cmp.compare(&key, &c.as_ref().key).is_lt());
+            assert!(in_order, "key does not belong before the cursor");
+            // The new node either becomes the left child of the current node or the right child
+            // of its predecessor, whichever of the two slots is free.
+            let slot = match self.current {
+                Some(c) if c.as_ref().left.is_none() => Some((c, Direction::Left)),
+                _ => prev.map(|p| (p, Direction::Right)),
+            };
+            self.map.insert_at(slot, key, value);
+        }
+    }
+
+    /// Removes the entry under the cursor and moves on to the next one. Does nothing on the
+    /// ghost position.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        let node = self.current?;
+        unsafe {
+            self.current = Node::successor(node);
+            Some(self.map.remove_node(node))
         }
     }
 }
 
 type Link<K, V> = Option<NonNull<Node<K, V>>>;
+/// The node below which a missing key belongs and the side it goes to, `None` for an empty tree.
+type Slot<K, V> = Option<(NonNull<Node<K, V>>, Direction)>;
 type Parant<K, V> = Option<NonNull<Node<K, V>>>;
 
-pub struct Node<K: PartialOrd, V> {
+pub struct Node<K, V> {
     parent: Parant<K, V>,
     key: K,
     value: V,
     priority: usize,
+    /// Number of nodes in the subtree rooted at this node.
+    size: usize,
     left: Link<K, V>,
     right: Link<K, V>,
 }
 
+/// Panics like the range queries of the std collections if `range` is inverted or excludes
+/// the same key on both ends.
+pub(crate) fn check_range<Q, C, R>(cmp: &C, range: &R)
+where
+    Q: ?Sized,
+    C: Comparator<Q>,
+    R: RangeBounds<Q>,
+{
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Excluded(s), Bound::Excluded(e)) if cmp.compare(s, e).is_eq() => {
+            panic!("range start and end are equal and excluded")
+        }
+        (Bound::Included(s) | Bound::Excluded(s), Bound::Included(e) | Bound::Excluded(e))
+            if cmp.compare(s, e).is_gt() =>
+        {
+            panic!("range start is greater than range end")
+        }
+        _ => {}
+    }
+}
+
 enum Direction {
     Left,
     Right,
 }
 
-impl<K: PartialOrd + Debug, V: Debug> Debug for Node<K, V> {
+/// Which keys a set operation on two subtrees keeps.
+#[derive(Clone, Copy)]
+struct SetOp {
+    /// Whether the keys present in only the first or only the second subtree are kept.
+    keep_only: (bool, bool),
+    /// Whether the keys present in both are kept, with the key of the first subtree.
+    keep_both: bool,
+    /// Whether the kept keys present in both take the value of the second subtree.
+    second_value_wins: bool,
+}
+
+impl SetOp {
+    const UNION: Self = Self {
+        keep_only: (true, true),
+        keep_both: true,
+        second_value_wins: true,
+    };
+    const INTERSECTION: Self = Self {
+        keep_only: (false, false),
+        keep_both: true,
+        second_value_wins: false,
+    };
+    const DIFFERENCE: Self = Self {
+        keep_only: (true, false),
+        keep_both: false,
+        second_value_wins: false,
+    };
+    const SYMMETRIC_DIFFERENCE: Self = Self {
+        keep_only: (true, true),
+        keep_both: false,
+        second_value_wins: false,
+    };
+
+    /// Whether the keys present only in the subtree in first place are kept, which is the
+    /// second subtree if the two `swapped` places.
+    fn keeps_only(self, swapped: bool) -> bool {
+        if swapped {
+            self.keep_only.1
+        } else {
+            self.keep_only.0
+        }
+    }
+}
+
+/// A pending step of [`Node::merge_sets`].
+enum SetStep<K, V> {
+    /// Merge two subtrees, which trade places with respect to the operation if `swapped`.
+    Merge {
+        a: Link<K, V>,
+        b: Link<K, V>,
+        swapped: bool,
+    },
+    /// Put `pivot` between the two subtrees merged last, or drop it along with `twin`, the
+    /// node with the same key from the other subtree.
+    Pivot {
+        pivot: NonNull<Node<K, V>>,
+        twin: Link<K, V>,
+        swapped: bool,
+    },
+}
+
+impl<K: Debug, V: Debug> Debug for Node<K, V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Node")
             .field("key", &self.key)
@@ -123,18 +1680,7 @@ impl<K: PartialOrd + Debug, V: Debug> Debug for Node<K, V> {
     }
 }
 
-impl<K: PartialOrd, V> Node<K, V> {
-    fn new(key: K, value: V, priority: usize) -> Box<Self> {
-        Box::new(Self {
-            parent: None,
-            key,
-            value,
-            priority,
-            left: None,
-            right: None,
-        })
-    }
-
+impl<K, V> Node<K, V> {
     fn get_highest_prio_child(&self) -> Option<Direction> {
         match (self.left, self.right) {
             (None, None) => None,
@@ -150,56 +1696,122 @@ impl<K: PartialOrd, V> Node<K, V> {
         }
     }
 
-    fn drop_children(mut self) {
-        if let Some(left) = self.left.take() {
-            let left = unsafe { Box::from_raw(left.as_ptr()) };
-            left.drop_children()
+    unsafe fn leftmost(mut node: NonNull<Self>) -> NonNull<Self> {
+        while let Some(l) = node.as_ref().left {
+            node = l;
         }
-        if let Some(right) = self.right {
-            let right = unsafe { Box::from_raw(right.as_ptr()) };
-            right.drop_children()
+        node
+    }
+
+    unsafe fn rightmost(mut node: NonNull<Self>) -> NonNull<Self> {
+        while let Some(r) = node.as_ref().right {
+            node = r;
         }
+        node
     }
 
-    fn get(&self, needle: K) -> Option<&V> {
-        match self.key.partial_cmp(&needle) {
-            Some(r) => match r {
-                Ordering::Greater => self
-                    .left
-                    .map(|l| unsafe { l.as_ref().get(needle) })
-                    .unwrap_or_default(),
-                Ordering::Equal => Some(&self.value),
-                Ordering::Less => self
-                    .right
-                    .map(|r| unsafe { r.as_ref().get(needle) })
-                    .unwrap_or_default(),
-            },
-            None => panic!("Failed to compare values"),
-        }
-    }
-
-    fn get_mut(&mut self, needle: K) -> Option<&mut V> {
-        match self.key.partial_cmp(&needle) {
-            Some(r) => match r {
-                Ordering::Greater => self
-                    .left
-                    .map(|mut l| unsafe { l.as_mut().get_mut(needle) })
-                    .unwrap_or_default(),
-                Ordering::Equal => Some(&mut self.value),
-                Ordering::Less => self
-                    .right
-                    .map(|mut r| unsafe { r.as_mut().get_mut(needle) })
-                    .unwrap_or_default(),
-            },
-            None => panic!("Failed to compare values"),
+    /// Returns the first node below `node` whose key is not before `bound`.
+    unsafe fn lower_bound<Q, C>(mut node: Link<K, V>, bound: Bound<&Q>, cmp: &C) -> Link<K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut found = None;
+        while let Some(n) = node {
+            let key = n.as_ref().key.borrow();
+            let inside = match bound {
+                Bound::Included(k) => cmp.compare(key, k).is_ge(),
+                Bound::Excluded(k) => cmp.compare(key, k).is_gt(),
+                Bound::Unbounded => true,
+            };
+            if inside {
+                found = Some(n);
+                node = n.as_ref().left;
+            } else {
+                node = n.as_ref().right;
+            }
+        }
+        found
+    }
+
+    /// Returns the last node below `node` whose key is not after `bound`.
+    unsafe fn upper_bound<Q, C>(mut node: Link<K, V>, bound: Bound<&Q>, cmp: &C) -> Link<K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut found = None;
+        while let Some(n) = node {
+            let key = n.as_ref().key.borrow();
+            let inside = match bound {
+                Bound::Included(k) => cmp.compare(key, k).is_le(),
+                Bound::Excluded(k) => cmp.compare(key, k).is_lt(),
+                Bound::Unbounded => true,
+            };
+            if inside {
+                found = Some(n);
+                node = n.as_ref().right;
+            } else {
+                node = n.as_ref().left;
+            }
+        }
+        found
+    }
+
+    /// Returns the node following `node` in key order, climbing up the `parent` pointers if
+    /// `node` has no right subtree.
+    unsafe fn successor(mut node: NonNull<Self>) -> Link<K, V> {
+        if let Some(r) = node.as_ref().right {
+            return Some(Self::leftmost(r));
+        }
+        while let Some(p) = node.as_ref().parent {
+            if p.as_ref().left == Some(node) {
+                return Some(p);
+            }
+            node = p;
+        }
+        None
+    }
+
+    /// Returns the node preceding `node` in key order.
+    unsafe fn predecessor(mut node: NonNull<Self>) -> Link<K, V> {
+        if let Some(l) = node.as_ref().left {
+            return Some(Self::rightmost(l));
+        }
+        while let Some(p) = node.as_ref().parent {
+            if p.as_ref().right == Some(node) {
+                return Some(p);
+            }
+            node = p;
+        }
+        None
+    }
+
+    /// Frees every node of the tree below `link` without recursing.
+    unsafe fn drop_tree(mut link: Link<K, V>) {
+        while let Some(mut n) = link {
+            if let Some(mut l) = n.as_mut().left.take() {
+                // Rotate the left child up, so that the tree eventually degenerates into a right
+                // spine which can be freed from the top.
+                n.as_mut().left = l.as_mut().right.take();
+                l.as_mut().right = Some(n);
+                link = Some(l);
+            } else {
+                link = n.as_ref().right;
+                drop(Box::from_raw(n.as_ptr()));
+            }
         }
     }
+
     fn new_with_parent(key: K, value: V, priority: usize, parent: Parant<K, V>) -> Box<Self> {
         Box::new(Self {
             parent,
             key,
             value,
             priority,
+            size: 1,
             left: None,
             right: None,
         })
@@ -208,36 +1820,322 @@ impl<K: PartialOrd, V> Node<K, V> {
     /// After a new insertion it is likely for the max heap structure of the tree to be gone
     /// so this function fixes it from the bottom up by rotating accordingly so we do not
     /// destroy the in order attribute of our search tree.
-    unsafe fn fix(parent: Parant<K, V>) {
-        if let Some(parent) = parent.map(|mut p| p.as_mut()) {
-            if parent
-                .left
-                .map(|l| l.as_ref().priority > parent.priority)
-                .unwrap_or(false)
-            {
-                parent.rotate_right();
-                Self::fix(
-                    parent
-                        .parent
-                        .expect("parent after rotation")
-                        .as_ref()
-                        .parent,
-                )
-            } else if parent
-                .right
-                .map(|r| r.as_ref().priority > parent.priority)
-                .unwrap_or(false)
-            {
-                parent.rotate_left();
-                Self::fix(
-                    parent
-                        .parent
-                        .expect("parent after rotation")
-                        .as_ref()
-                        .parent,
-                )
+    unsafe fn fix(mut parent: Parant<K, V>) {
+        while let Some(p) = parent.map(|mut p| p.as_mut()) {
+            let outranks = |c: Link<K, V>| c.map(|c| c.as_ref().priority > p.priority);
+            if outranks(p.left).unwrap_or(false) {
+                p.rotate_right();
+            } else if outranks(p.right).unwrap_or(false) {
+                p.rotate_left();
+            } else {
+                break;
+            }
+            parent = p.parent.expect("parent after rotation").as_ref().parent;
+        }
+    }
+
+    unsafe fn first(link: Link<K, V>) -> Link<K, V> {
+        link.map(|n| Self::leftmost(n))
+    }
+
+    unsafe fn last(link: Link<K, V>) -> Link<K, V> {
+        link.map(|n| Self::rightmost(n))
+    }
+
+    unsafe fn set_parent(link: Link<K, V>, parent: Parant<K, V>) {
+        if let Some(mut n) = link {
+            n.as_mut().parent = parent;
+        }
+    }
+
+    /// Replaces both children of `node` at once.
+    ///
+    /// The set operations free nodes of the old children, so the size of `node` can only be
+    /// recomputed once both links are replaced.
+    unsafe fn set_children(mut node: NonNull<Self>, left: Link<K, V>, right: Link<K, V>) {
+        node.as_mut().left = left;
+        node.as_mut().right = right;
+        Self::set_parent(left, Some(node));
+        Self::set_parent(right, Some(node));
+        node.as_mut().update_size();
+    }
+
+    /// Links `node` as the `side` child of `hook`, or makes it the root if there is no hook.
+    unsafe fn attach(root: &mut Link<K, V>, hook: Parant<K, V>, side: Direction, node: Link<K, V>) {
+        match hook {
+            Some(mut h) => match side {
+                Direction::Left => h.as_mut().left = node,
+                Direction::Right => h.as_mut().right = node,
+            },
+            None => *root = node,
+        }
+        Self::set_parent(node, hook);
+    }
+
+    /// Recomputes the sizes of `node` and all of its ancestors.
+    unsafe fn update_path(mut node: Link<K, V>) {
+        while let Some(mut n) = node {
+            n.as_mut().update_size();
+            node = n.as_ref().parent;
+        }
+    }
+
+    /// Splits the subtree into the keys before `key` and the keys after and including `key`.
+    ///
+    /// Walks down a single path, handing every node to the left or the right tree.
+    unsafe fn split<Q, C>(node: Link<K, V>, key: &Q, cmp: &C) -> (Link<K, V>, Link<K, V>)
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        Self::split_by(node, |k| cmp.compare(k.borrow(), key).is_lt())
+    }
+
+    /// Splits the subtree into the keys for which `is_left` holds and the keys after them.
+    ///
+    /// `is_left` has to hold for a prefix of the keys.
+    unsafe fn split_by<F: Fn(&K) -> bool>(
+        mut node: Link<K, V>,
+        is_left: F,
+    ) -> (Link<K, V>, Link<K, V>) {
+        let (mut left, mut right) = (None, None);
+        // The last node handed to each tree, whose inner child receives the next node.
+        let (mut left_hook, mut right_hook) = (None, None);
+        while let Some(n) = node {
+            if is_left(&n.as_ref().key) {
+                Self::attach(&mut left, left_hook, Direction::Right, Some(n));
+                left_hook = Some(n);
+                node = n.as_ref().right;
+            } else {
+                Self::attach(&mut right, right_hook, Direction::Left, Some(n));
+                right_hook = Some(n);
+                node = n.as_ref().left;
+            }
+        }
+        Self::attach(&mut left, left_hook, Direction::Right, None);
+        Self::attach(&mut right, right_hook, Direction::Left, None);
+        Self::update_path(left_hook);
+        Self::update_path(right_hook);
+        (left, right)
+    }
+
+    /// Splits the subtree into the keys before `key`, the detached node holding `key` and the
+    /// keys after `key`.
+    unsafe fn split_at<C: Comparator<K>>(
+        node: Link<K, V>,
+        key: &K,
+        cmp: &C,
+    ) -> (Link<K, V>, Link<K, V>, Link<K, V>) {
+        let (left, mut right) = Self::split(node, key, cmp);
+        let Some(mut first) = Self::first(right) else {
+            return (left, None, None);
+        };
+        if cmp.compare(&first.as_ref().key, key).is_ne() {
+            return (left, None, right);
+        }
+        // The smallest node has no left child, so its right child simply takes its place.
+        let parent = first.as_ref().parent;
+        let child = first.as_mut().right.take();
+        Self::attach(&mut right, parent, Direction::Left, child);
+        Self::shrink_path(parent);
+        first.as_mut().size = 1;
+        (left, Some(first), right)
+    }
+
+    /// Appends `node` as the largest node to the tree whose right spine, from the root down, is
+    /// `spine`.
+    ///
+    /// Nodes with a lower priority move into the left subtree of `node`, where they are
+    /// complete, so their sizes can be settled.
+    unsafe fn push_spine(spine: &mut Vec<NonNull<Self>>, mut node: NonNull<Self>) {
+        let priority = node.as_ref().priority;
+        let mut left = None;
+        while let Some(mut top) = spine.last().copied() {
+            if top.as_ref().priority >= priority {
+                break;
+            }
+            spine.pop();
+            top.as_mut().update_size();
+            left = Some(top);
+        }
+        node.as_mut().left = left;
+        node.as_mut().right = None;
+        node.as_mut().parent = spine.last().copied();
+        Self::set_parent(left, Some(node));
+        if let Some(mut top) = spine.last().copied() {
+            top.as_mut().right = Some(node);
+        }
+        spine.push(node);
+    }
+
+    /// Settles the sizes along `spine`, leaving it empty, and returns the root of its tree.
+    unsafe fn finish_spine(spine: &mut Vec<NonNull<Self>>) -> Link<K, V> {
+        let mut root = None;
+        while let Some(mut top) = spine.pop() {
+            top.as_mut().update_size();
+            root = Some(top);
+        }
+        root
+    }
+
+    /// Merges two subtrees where all keys of `left` are smaller than the keys of `right`.
+    unsafe fn merge(mut left: Link<K, V>, mut right: Link<K, V>) -> Link<K, V> {
+        let mut root = None;
+        let (mut hook, mut side) = (None, Direction::Left);
+        loop {
+            match (left, right) {
+                (None, rest) | (rest, None) => {
+                    Self::attach(&mut root, hook, side, rest);
+                    break;
+                }
+                (Some(l), Some(r)) => {
+                    if l.as_ref().priority > r.as_ref().priority {
+                        Self::attach(&mut root, hook, side, left);
+                        (hook, side) = (left, Direction::Right);
+                        left = l.as_ref().right;
+                    } else {
+                        Self::attach(&mut root, hook, side, right);
+                        (hook, side) = (right, Direction::Left);
+                        right = r.as_ref().left;
+                    }
+                }
+            }
+        }
+        Self::update_path(hook);
+        root
+    }
+
+    /// Merges two arbitrary subtrees by the rules of `op`, pivoting on the root with the higher
+    /// priority and splitting the other subtree at its key.
+    ///
+    /// The pending merges live on an explicit stack, so deep treaps cannot overflow the call
+    /// stack. Each pivot is finished once the merges of both of its sides are done.
+    unsafe fn merge_sets<C: Comparator<K>>(
+        a: Link<K, V>,
+        b: Link<K, V>,
+        op: SetOp,
+        cmp: &C,
+    ) -> Link<K, V> {
+        let mut steps = vec![SetStep::Merge {
+            a,
+            b,
+            swapped: false,
+        }];
+        // The merged subtrees, in key order, waiting for the pivot between them.
+        let mut done = Vec::new();
+        while let Some(step) = steps.pop() {
+            match step {
+                SetStep::Merge {
+                    a: Some(a),
+                    b: Some(b),
+                    swapped,
+                } => {
+                    let (pivot, other, swapped) = if a.as_ref().priority < b.as_ref().priority {
+                        (b, a, !swapped)
+                    } else {
+                        (a, b, swapped)
+                    };
+                    let (l, twin, r) = Self::split_at(Some(other), &pivot.as_ref().key, cmp);
+                    steps.push(SetStep::Pivot {
+                        pivot,
+                        twin,
+                        swapped,
+                    });
+                    steps.push(SetStep::Merge {
+                        a: pivot.as_ref().right,
+                        b: r,
+                        swapped,
+                    });
+                    steps.push(SetStep::Merge {
+                        a: pivot.as_ref().left,
+                        b: l,
+                        swapped,
+                    });
+                }
+                SetStep::Merge {
+                    a,
+                    b: None,
+                    swapped,
+                } => {
+                    done.push(Self::keep_if(a, op.keeps_only(swapped)));
+                }
+                SetStep::Merge {
+                    a: None,
+                    b,
+                    swapped,
+                } => {
+                    done.push(Self::keep_if(b, op.keeps_only(!swapped)));
+                }
+                SetStep::Pivot {
+                    mut pivot,
+                    twin,
+                    swapped,
+                } => {
+                    let (Some(right), Some(left)) = (done.pop(), done.pop()) else {
+                        unreachable!("both sides of a pivot are merged before it");
+                    };
+                    let kept = match twin {
+                        Some(twin) => {
+                            let mut twin = Box::from_raw(twin.as_ptr());
+                            let pivot = pivot.as_mut();
+                            // The pivot node stays, so it takes over the key of the first
+                            // subtree and the value that wins.
+                            if swapped {
+                                std::mem::swap(&mut pivot.key, &mut twin.key);
+                            }
+                            if swapped != op.second_value_wins {
+                                std::mem::swap(&mut pivot.value, &mut twin.value);
+                            }
+                            op.keep_both
+                        }
+                        None => op.keeps_only(swapped),
+                    };
+                    done.push(if kept {
+                        Self::set_children(pivot, left, right);
+                        Some(pivot)
+                    } else {
+                        drop(Box::from_raw(pivot.as_ptr()));
+                        Self::merge(left, right)
+                    });
+                }
             }
         }
+        done.pop().flatten()
+    }
+
+    /// Returns the subtree if `kept`, and frees it otherwise.
+    unsafe fn keep_if(link: Link<K, V>, kept: bool) -> Link<K, V> {
+        if kept {
+            return link;
+        }
+        Self::drop_tree(link);
+        None
+    }
+
+    unsafe fn size_of(link: Link<K, V>) -> usize {
+        link.map(|n| n.as_ref().size).unwrap_or(0)
+    }
+
+    /// Recomputes the size of this node from its children.
+    unsafe fn update_size(&mut self) {
+        self.size = 1 + Self::size_of(self.left) + Self::size_of(self.right);
+    }
+
+    /// Accounts for a node that was added below `node` on every node up to the root.
+    unsafe fn grow_path(mut node: Link<K, V>) {
+        while let Some(mut n) = node {
+            n.as_mut().size += 1;
+            node = n.as_ref().parent;
+        }
+    }
+
+    /// Accounts for a node that was removed below `node` on every node up to the root.
+    unsafe fn shrink_path(mut node: Link<K, V>) {
+        while let Some(mut n) = node {
+            n.as_mut().size -= 1;
+            node = n.as_ref().parent;
+        }
     }
 
     unsafe fn rotate_right(&mut self) {
@@ -246,7 +2144,7 @@ impl<K: PartialOrd, V> Node<K, V> {
         };
         self.left = new_parent.as_mut().right.take();
         if let Some(p) = self.parent.map(|mut p| p.as_mut()) {
-            if p.left.map(|l| l.as_ref().key == self.key).unwrap_or(false) {
+            if p.left == NonNull::new(self as *mut Self) {
                 p.left = Some(new_parent);
             } else {
                 p.right = Some(new_parent);
@@ -258,6 +2156,8 @@ impl<K: PartialOrd, V> Node<K, V> {
         new_parent.as_mut().parent = self.parent.take();
         self.parent = Some(new_parent);
         new_parent.as_mut().right = NonNull::new(self as *mut Self);
+        self.update_size();
+        new_parent.as_mut().update_size();
     }
 
     unsafe fn rotate_left(&mut self) {
@@ -266,7 +2166,7 @@ impl<K: PartialOrd, V> Node<K, V> {
         };
         self.right = new_parent.as_mut().left.take();
         if let Some(p) = self.parent.map(|mut p| p.as_mut()) {
-            if p.left.map(|l| l.as_ref().key == self.key).unwrap_or(false) {
+            if p.left == NonNull::new(self as *mut Self) {
                 p.left = Some(new_parent);
             } else {
                 p.right = Some(new_parent);
@@ -278,78 +2178,7 @@ impl<K: PartialOrd, V> Node<K, V> {
         new_parent.as_mut().parent = self.parent.take();
         self.parent = Some(new_parent);
         new_parent.as_mut().left = NonNull::new(self as *mut Self);
-    }
-
-    unsafe fn insert(mut dst: NonNull<Node<K, V>>, key: K, mut value: V, priority: usize) {
-        let target = dst.as_mut();
-        match target.key.partial_cmp(&key) {
-            Some(Ordering::Equal) => std::mem::swap(&mut target.value, &mut value),
-            Some(Ordering::Greater) => match target.left.as_ref() {
-                Some(l) => {
-                    Self::insert(*l, key, value, priority);
-                }
-                None => {
-                    let new_element = Node::new_with_parent(key, value, priority, Some(dst));
-                    target.left = NonNull::new(Box::into_raw(new_element));
-                    Self::fix(Some(dst));
-                }
-            },
-            Some(Ordering::Less) => match target.right.as_ref() {
-                Some(r) => {
-                    Self::insert(*r, key, value, priority);
-                }
-                None => {
-                    let new_element = Node::new_with_parent(key, value, priority, Some(dst));
-                    target.right = NonNull::new(Box::into_raw(new_element));
-                    Self::fix(Some(dst));
-                }
-            },
-            None => panic!("Failed to compare"),
-        }
-    }
-
-    unsafe fn remove(mut node: NonNull<Node<K, V>>, key: K) -> Option<V> {
-        while node.as_ref().key != key {
-            match node
-                .as_ref()
-                .key
-                .partial_cmp(&key)
-                .expect("to be able to compare keys")
-            {
-                Ordering::Greater => {
-                    if let Some(l) = node.as_ref().left {
-                        node = l;
-                    } else {
-                        return None;
-                    }
-                }
-                Ordering::Less => {
-                    if let Some(r) = node.as_ref().right {
-                        node = r;
-                    } else {
-                        return None;
-                    }
-                }
-                Ordering::Equal => {}
-            }
-        }
-        while let Some(d) = node.as_ref().get_highest_prio_child() {
-            match d {
-                Direction::Left => node.as_mut().rotate_right(),
-                Direction::Right => node.as_mut().rotate_left(),
-            }
-        }
-        let mut parent = node.as_ref().parent.unwrap();
-        if parent
-            .as_ref()
-            .left
-            .map(|l| l.as_ref().key == key)
-            .unwrap_or(false)
-        {
-            let d = Box::from_raw(parent.as_mut().left.take().unwrap().as_ptr());
-            return Some(d.value);
-        }
-        let d = Box::from_raw(parent.as_mut().right.take().unwrap().as_ptr());
-        return Some(d.value);
+        self.update_size();
+        new_parent.as_mut().update_size();
     }
 }
@@ -0,0 +1,87 @@
+use super::EventEmmiter;
+use futures_core::Stream;
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// Source of the delays between two emitted events.
+///
+/// Implement this for the sleep primitive of your runtime, or for a mock clock in tests.
+pub trait Timer {
+    type Sleep: Future<Output = ()>;
+
+    fn sleep(&mut self, duration: Duration) -> Self::Sleep;
+}
+
+/// The rate at which an [`EventStream`] emits events.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rate {
+    /// Emit an event after every `Duration`.
+    Constant(Duration),
+    /// Emit events as a Poisson process, i.e. with exponentially distributed gaps averaging
+    /// `mean`.
+    Poisson { mean: Duration },
+}
+
+impl Rate {
+    fn next_delay(&self, rng: &mut ThreadRng) -> Duration {
+        match self {
+            Rate::Constant(d) => *d,
+            Rate::Poisson { mean } => {
+                let u: f64 = rng.gen_range(0.0..1.0);
+                Duration::from_secs_f64(-mean.as_secs_f64() * (1.0 - u).ln())
+            }
+        }
+    }
+}
+
+/// An endless [`Stream`] of events drawn from an [`EventEmmiter`], created by
+/// [`EventEmmiter::stream`].
+pub struct EventStream<'a, const S: usize, E, T: Timer> {
+    emitter: &'a EventEmmiter<S, E>,
+    rate: Rate,
+    timer: T,
+    rng: ThreadRng,
+    sleep: Option<Pin<Box<T::Sleep>>>,
+}
+
+impl<const S: usize, E> EventEmmiter<S, E> {
+    /// Returns an endless stream that draws an event after every delay of `rate`, waiting for
+    /// each delay through `timer`.
+    ///
+    /// No delay is drawn before the stream is first polled.
+    pub fn stream<T: Timer>(&self, rate: Rate, timer: T) -> EventStream<'_, S, E, T> {
+        EventStream {
+            emitter: self,
+            rate,
+            timer,
+            rng: thread_rng(),
+            sleep: None,
+        }
+    }
+}
+
+impl<'a, const S: usize, E, T: Timer + Unpin> Stream for EventStream<'a, S, E, T> {
+    type Item = &'a E;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let sleep = this.sleep.get_or_insert_with(|| {
+            let delay = this.rate.next_delay(&mut this.rng);
+            Box::pin(this.timer.sleep(delay))
+        });
+        if sleep.as_mut().poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        this.sleep = None;
+        Poll::Ready(Some(this.emitter.generate()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
@@ -0,0 +1,161 @@
+use super::{rtree::Entry, RTreeMap};
+use crate::compare::{Comparator, Natural};
+use std::{
+    borrow::Borrow,
+    ops::Deref,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Hands out the ids that tie savepoints to their transaction.
+static NEXT_TRANSACTION: AtomicUsize = AtomicUsize::new(0);
+
+impl<K, V, C: Comparator<K>> RTreeMap<K, V, C> {
+    /// Starts a transaction that records how to undo every change it makes to the map.
+    ///
+    /// The transaction rolls back unless it is committed.
+    pub fn begin(&mut self) -> Transaction<'_, K, V, C> {
+        Transaction {
+            id: NEXT_TRANSACTION.fetch_add(1, Ordering::Relaxed),
+            map: self,
+            log: Vec::new(),
+            savepoints: Vec::new(),
+            next_id: 0,
+        }
+    }
+}
+
+/// A batch of changes to a [`RTreeMap`] that can be rolled back as a whole or to a
+/// [`Savepoint`].
+///
+/// Every change pushes its inverse operation onto an undo log, so rolling back k changes takes
+/// O(k log n) expected time. The map can be read through the transaction, which derefs to it.
+/// Dropping the transaction without [`Transaction::commit`] rolls back all of its changes.
+pub struct Transaction<'a, K, V, C: Comparator<K> = Natural> {
+    id: usize,
+    map: &'a mut RTreeMap<K, V, C>,
+    log: Vec<Undo<K, V>>,
+    /// The ids of the live savepoints with the length of the log when they were taken.
+    savepoints: Vec<(usize, usize)>,
+    next_id: usize,
+}
+
+/// The inverse of a change to the map.
+enum Undo<K, V> {
+    Remove(K),
+    Insert(K, V),
+}
+
+/// A position in the undo log of a [`Transaction`] to roll back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Savepoint {
+    transaction: usize,
+    index: usize,
+    id: usize,
+}
+
+impl<'a, K, V, C: Comparator<K>> Transaction<'a, K, V, C> {
+    /// Inserts the entry, returning the previous value if the key was already present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        match self.map.entry(key) {
+            Entry::Occupied(mut o) => {
+                let old = o.insert(value);
+                self.log.push(Undo::Insert(o.key().clone(), old.clone()));
+                Some(old)
+            }
+            Entry::Vacant(v) => {
+                self.log.push(Undo::Remove(v.key().clone()));
+                v.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        V: Clone,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let (key, value) = self.map.remove_entry(key)?;
+        self.log.push(Undo::Insert(key, value.clone()));
+        Some(value)
+    }
+
+    /// Returns the number of changes that rolling back the whole transaction would undo.
+    pub fn changes(&self) -> usize {
+        self.log.len()
+    }
+
+    /// Marks the current state of the map, so that later changes can be rolled back to it.
+    pub fn savepoint(&mut self) -> Savepoint {
+        let sp = Savepoint {
+            transaction: self.id,
+            index: self.savepoints.len(),
+            id: self.next_id,
+        };
+        self.next_id += 1;
+        self.savepoints.push((sp.id, self.log.len()));
+        sp
+    }
+
+    /// Undoes the changes made since `sp` was taken.
+    ///
+    /// `sp` stays valid, while the savepoints taken after it are released.
+    ///
+    /// Panics if `sp` was taken by another transaction or was released by rolling back to an
+    /// earlier savepoint.
+    pub fn rollback_to(&mut self, sp: Savepoint) {
+        assert_eq!(
+            sp.transaction, self.id,
+            "savepoint belongs to another transaction"
+        );
+        let Some(&(id, len)) = self.savepoints.get(sp.index) else {
+            panic!("savepoint was released");
+        };
+        assert_eq!(id, sp.id, "savepoint was released");
+        self.savepoints.truncate(sp.index + 1);
+        self.undo(len);
+    }
+
+    /// Undoes all changes of the transaction.
+    pub fn rollback(mut self) {
+        self.undo(0);
+    }
+
+    /// Keeps the changes of the transaction.
+    pub fn commit(mut self) {
+        self.log.clear();
+    }
+
+    fn undo(&mut self, len: usize) {
+        for undo in self.log.drain(len..).rev() {
+            match undo {
+                Undo::Remove(key) => {
+                    self.map.remove(&key);
+                }
+                Undo::Insert(key, value) => {
+                    self.map.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>> Deref for Transaction<'a, K, V, C> {
+    type Target = RTreeMap<K, V, C>;
+
+    fn deref(&self) -> &Self::Target {
+        self.map
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>> Drop for Transaction<'a, K, V, C> {
+    fn drop(&mut self) {
+        self.undo(0);
+    }
+}
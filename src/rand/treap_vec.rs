@@ -0,0 +1,351 @@
+use rand::{thread_rng, Rng};
+use std::{
+    fmt::Debug,
+    iter::FusedIterator,
+    mem,
+    ops::{Bound, Index, IndexMut, RangeBounds},
+};
+
+/// A sequence backed by an implicit treap, where the position of an element is given by the
+/// sizes of the subtrees to its left instead of a stored key.
+///
+/// Inserting or removing at any index, splitting, appending and reversing a range all take
+/// O(log n) expected time.
+///
+/// The treap does not share the nodes of [`RTreeMap`](super::RTreeMap). Those carry a key and
+/// `parent` pointers, which its cursors and iterators follow without descending from the
+/// root. Reversing a range here only flags the root of its subtree, and the flags are pushed
+/// down on the next descent, so a walk along `parent` pointers would see elements in their
+/// stale order.
+pub struct TreapVec<T> {
+    root: Link<T>,
+}
+
+impl<T: Debug> Debug for TreapVec<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> TreapVec<T> {
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        Node::size_of(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn get(&self, mut index: usize) -> Option<&T> {
+        let mut node = self.root.as_deref();
+        let mut flipped = false;
+        while let Some(n) = node {
+            flipped ^= n.reversed;
+            let (left, right) = n.children(flipped);
+            let left_size = Node::size_of(left);
+            match index.checked_sub(left_size) {
+                None => node = left.as_deref(),
+                Some(0) => return Some(&n.value),
+                Some(rest) => {
+                    index = rest - 1;
+                    node = right.as_deref();
+                }
+            }
+        }
+        None
+    }
+
+    pub fn get_mut(&mut self, mut index: usize) -> Option<&mut T> {
+        let mut node = self.root.as_deref_mut();
+        while let Some(n) = node {
+            n.push_down();
+            let left_size = Node::size_of(&n.left);
+            match index.checked_sub(left_size) {
+                None => node = n.left.as_deref_mut(),
+                Some(0) => return Some(&mut n.value),
+                Some(rest) => {
+                    index = rest - 1;
+                    node = n.right.as_deref_mut();
+                }
+            }
+        }
+        None
+    }
+
+    /// Inserts `value` at `index`, shifting all elements after it to the right.
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, value: T) {
+        assert!(index <= self.len(), "insertion index out of bounds");
+        let node = Some(Box::new(Node::new(value, thread_rng().gen())));
+        let (left, right) = Node::split(self.root.take(), index);
+        self.root = Node::merge(Node::merge(left, node), right);
+    }
+
+    /// Removes and returns the element at `index`, shifting all elements after it to the left.
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len(), "removal index out of bounds");
+        let (left, right) = Node::split(self.root.take(), index);
+        let (node, right) = Node::split(right, 1);
+        self.root = Node::merge(left, right);
+        node.map(|n| n.value).unwrap()
+    }
+
+    pub fn push(&mut self, value: T) {
+        let node = Some(Box::new(Node::new(value, thread_rng().gen())));
+        self.root = Node::merge(self.root.take(), node);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let len = self.len();
+        (len > 0).then(|| self.remove(len - 1))
+    }
+
+    /// Splits the sequence into the elements before `at` and the elements from `at` on.
+    ///
+    /// Panics if `at > len`.
+    pub fn split_at(mut self, at: usize) -> (Self, Self) {
+        assert!(at <= self.len(), "split index out of bounds");
+        let (left, right) = Node::split(self.root.take(), at);
+        self.root = left;
+        let mut other = Self::new();
+        other.root = right;
+        (self, other)
+    }
+
+    /// Moves all elements of `other` to the end of `self`, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        self.root = Node::merge(self.root.take(), other.root.take());
+    }
+
+    /// Reverses the order of the elements in `range`.
+    ///
+    /// Only flags the root of the range, the flag is pushed down to the children lazily.
+    ///
+    /// Panics if the range is out of bounds or its start is greater than its end.
+    pub fn reverse<R: RangeBounds<usize>>(&mut self, range: R) {
+        let len = self.len();
+        // A bound that overflows lies past the end anyway.
+        let start = match range.start_bound() {
+            Bound::Included(&s) => Some(s),
+            Bound::Excluded(&s) => s.checked_add(1),
+            Bound::Unbounded => Some(0),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&e) => e.checked_add(1),
+            Bound::Excluded(&e) => Some(e),
+            Bound::Unbounded => Some(len),
+        };
+        let (start, end) = match (start, end) {
+            (Some(s), Some(e)) if s <= e && e <= len => (s, e),
+            _ => panic!("range out of bounds"),
+        };
+        let (rest, right) = Node::split(self.root.take(), end);
+        let (left, mut middle) = Node::split(rest, start);
+        if let Some(m) = middle.as_deref_mut() {
+            m.reversed ^= true;
+        }
+        self.root = Node::merge(Node::merge(left, middle), right);
+    }
+
+    /// Returns an iterator over the elements in index order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            len: self.len(),
+        };
+        iter.push_front(self.root.as_deref(), false);
+        iter
+    }
+}
+
+impl<T> Default for TreapVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for TreapVec<T> {
+    fn drop(&mut self) {
+        // Unlink the nodes one by one so that dropping a deep tree does not recurse.
+        let mut stack = Vec::from_iter(self.root.take());
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+impl<T> Index<usize> for TreapVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl<T> IndexMut<usize> for TreapVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).expect("index out of bounds")
+    }
+}
+
+impl<T> FromIterator<T> for TreapVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl<T> Extend<T> for TreapVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a TreapVec<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the elements of a [`TreapVec`] in index order.
+///
+/// Pending reversals cannot be pushed down through a shared reference, so every node on the
+/// stack remembers whether its children are swapped.
+pub struct Iter<'a, T> {
+    stack: Vec<(&'a Node<T>, bool)>,
+    len: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_front(&mut self, mut node: Option<&'a Node<T>>, mut flipped: bool) {
+        while let Some(n) = node {
+            flipped ^= n.reversed;
+            self.stack.push((n, flipped));
+            node = n.children(flipped).0.as_deref();
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, flipped) = self.stack.pop()?;
+        self.len -= 1;
+        self.push_front(node.children(flipped).1.as_deref(), flipped);
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+pub struct Node<T> {
+    value: T,
+    priority: usize,
+    /// Number of nodes in the subtree rooted at this node.
+    size: usize,
+    /// Whether the subtree below this node still has to be mirrored.
+    reversed: bool,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> Node<T> {
+    fn new(value: T, priority: usize) -> Self {
+        Self {
+            value,
+            priority,
+            size: 1,
+            reversed: false,
+            left: None,
+            right: None,
+        }
+    }
+
+    fn size_of(link: &Link<T>) -> usize {
+        link.as_ref().map_or(0, |n| n.size)
+    }
+
+    fn update_size(&mut self) {
+        self.size = 1 + Self::size_of(&self.left) + Self::size_of(&self.right);
+    }
+
+    /// The children in index order, given whether the node is mirrored.
+    fn children(&self, flipped: bool) -> (&Link<T>, &Link<T>) {
+        if flipped {
+            (&self.right, &self.left)
+        } else {
+            (&self.left, &self.right)
+        }
+    }
+
+    /// Applies a pending reversal to the children and hands it on to their subtrees.
+    fn push_down(&mut self) {
+        if mem::take(&mut self.reversed) {
+            mem::swap(&mut self.left, &mut self.right);
+            for child in [&mut self.left, &mut self.right].into_iter().flatten() {
+                child.reversed ^= true;
+            }
+        }
+    }
+
+    /// Splits the subtree into its first `at` elements and the rest.
+    fn split(link: Link<T>, at: usize) -> (Link<T>, Link<T>) {
+        let Some(mut n) = link else {
+            return (None, None);
+        };
+        n.push_down();
+        let left_size = Self::size_of(&n.left);
+        if at <= left_size {
+            let (a, b) = Self::split(n.left.take(), at);
+            n.left = b;
+            n.update_size();
+            (a, Some(n))
+        } else {
+            let (a, b) = Self::split(n.right.take(), at - left_size - 1);
+            n.right = a;
+            n.update_size();
+            (Some(n), b)
+        }
+    }
+
+    /// Concatenates two subtrees, keeping the node with the higher priority on top.
+    fn merge(a: Link<T>, b: Link<T>) -> Link<T> {
+        match (a, b) {
+            (None, b) => b,
+            (a, None) => a,
+            (Some(mut x), Some(mut y)) => Some(if x.priority > y.priority {
+                x.push_down();
+                x.right = Self::merge(x.right.take(), Some(y));
+                x.update_size();
+                x
+            } else {
+                y.push_down();
+                y.left = Self::merge(Some(x), y.left.take());
+                y.update_size();
+                y
+            }),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use num_integer::Integer;
 
+#[allow(dead_code)]
 pub struct RangeQuery<T: Integer> {
     inner: Vec<T>,
 }
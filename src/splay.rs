@@ -1,76 +1,541 @@
-use std::{cmp::Ordering, fmt::Debug, ptr::NonNull};
+use crate::{
+    compare::{Comparator, Natural},
+    invariant::InvariantError,
+};
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    marker::PhantomData,
+    mem,
+    ops::Index,
+    ptr::NonNull,
+};
 
-/// TODO: Actually implement the splay operation
-
-pub struct SplayTree<K: PartialOrd, V> {
+pub struct SplayTree<K, V, C = Natural> {
     root: Link<K, V>,
+    len: usize,
+    cmp: C,
 }
 
-impl<K: PartialOrd + Debug, V: Debug> Debug for SplayTree<K, V> {
+impl<K: Debug, V: Debug, C> Debug for SplayTree<K, V, C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!(
-            "{:?}",
-            &self.root.map(|p| unsafe { p.as_ref() })
-        ))
+        f.debug_map().entries(self.iter()).finish()
     }
 }
 
-impl<K: PartialOrd, V> SplayTree<K, V> {
+// The tree owns its nodes exclusively, the raw pointers never escape into shared state.
+unsafe impl<K: Send, V: Send, C: Send> Send for SplayTree<K, V, C> {}
+unsafe impl<K: Sync, V: Sync, C: Sync> Sync for SplayTree<K, V, C> {}
+
+impl<K, V> SplayTree<K, V> {
     pub fn new() -> Self {
-        Self { root: None }
+        Self::with_comparator(Natural)
     }
+}
 
-    pub fn insert(&mut self, key: K, value: V) {
-        if let Some(root) = self.root.as_ref() {
-            unsafe { Node::insert(root.clone(), key, value) };
-            // It might happen that we are no longer referencing the root node.
-            if unsafe { self.root.unwrap().as_ref().parent.is_some() } {
-                self.root = unsafe { self.root.unwrap().as_ref().parent };
-            }
-        } else {
+impl<K, V, C> SplayTree<K, V, C> {
+    /// Creates an empty tree that orders its keys by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            root: None,
+            len: 0,
+            cmp,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts the entry and splays it to the root, returning the previous value for `key` if
+    /// there was one.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V>
+    where
+        C: Comparator<K>,
+    {
+        let Some(mut node) = self.root else {
             self.root = NonNull::new(Box::into_raw(Node::new(key, value)));
+            self.len = 1;
+            return None;
+        };
+        let mut old = None;
+        unsafe {
+            loop {
+                let n = node.as_mut();
+                let next = match self.cmp.compare(&n.key, &key) {
+                    Ordering::Equal => {
+                        old = Some(mem::replace(&mut n.value, value));
+                        break;
+                    }
+                    Ordering::Greater => &mut n.left,
+                    Ordering::Less => &mut n.right,
+                };
+                match *next {
+                    Some(child) => node = child,
+                    None => {
+                        let new_element = Node::new_with_parent(key, value, Some(node));
+                        node = NonNull::from(Box::leak(new_element));
+                        *next = Some(node);
+                        self.len += 1;
+                        break;
+                    }
+                }
+            }
+            Node::splay(node);
         }
+        self.root = Some(node);
+        old
     }
 
-    pub fn remove(&mut self, key: K) -> Option<V> {
-        if let Some(mut root) = self.root.take() {
-            // we need to delete the root node so we have to replace it now to keep a reference to
-            // the tree.
-            unsafe {
-                if root.as_mut().key == key {
-                    self.root = root.as_ref().parent;
-                } else {
-                    // Since we took the root node we will have to put it back in case it is not
-                    // the match.
-                    self.root = Some(root);
+    /// Removes the entry by splaying it to the root and joining its two subtrees.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut node = self.find(key)?;
+        unsafe {
+            Node::splay(node);
+            let (left, right) = (node.as_mut().left.take(), node.as_mut().right.take());
+            self.root = match left {
+                None => right,
+                Some(mut left) => {
+                    left.as_mut().parent = None;
+                    // The largest node of the left subtree ends up without a right child once
+                    // it is splayed to the top.
+                    let mut max = Node::rightmost(left);
+                    Node::splay(max);
+                    max.as_mut().right = right;
+                    Some(max)
                 }
-                return Node::remove(root, key);
+            };
+            if let Some(mut r) = right {
+                r.as_mut().parent = if self.root == right { None } else { self.root };
             }
-        } else {
-            None
+            self.len -= 1;
+            let node = Box::from_raw(node.as_ptr());
+            Some(node.value)
+        }
+    }
+
+    /// Removes and returns the entry with the smallest key.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let node = unsafe { Node::leftmost(self.root?) };
+        Some(unsafe { self.unlink_end(node, true) })
+    }
+
+    /// Removes and returns the entry with the largest key.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let node = unsafe { Node::rightmost(self.root?) };
+        Some(unsafe { self.unlink_end(node, false) })
+    }
+
+    /// Splays the smallest or the largest node to the root, where it lacks the child on the
+    /// outer side, and replaces it by its only subtree.
+    unsafe fn unlink_end(&mut self, mut node: NonNull<Node<K, V>>, first: bool) -> (K, V) {
+        Node::splay(node);
+        let n = node.as_mut();
+        self.root = if first { n.right.take() } else { n.left.take() };
+        if let Some(mut r) = self.root {
+            r.as_mut().parent = None;
+        }
+        self.len -= 1;
+        let node = Box::from_raw(node.as_ptr());
+        (node.key, node.value)
+    }
+
+    /// Returns whether the tree holds `needle`, without splaying like [`SplayTree::get`].
+    pub fn contains<Q>(&self, needle: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.find(needle).is_some()
+    }
+
+    /// Looks up `key` without splaying, so that lookups only need a shared reference.
+    ///
+    /// The lookup walks down to the depth of `key`, which is O(n) in the worst case, like
+    /// after sorted inserts left a single path of nodes. [`SplayTree::get_mut`] splays and
+    /// keeps the amortized O(log n) bound.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.find(key).map(|n| unsafe { &(*n.as_ptr()).value })
+    }
+
+    /// Looks up `key` and splays it to the root, in O(log n) amortized time.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.splay_to(key)
+            .map(|n| unsafe { &mut (*n.as_ptr()).value })
+    }
+
+    /// Splays the node holding `key` to the root and returns it. Without such a node, the last
+    /// node on the search path is splayed instead, so that the walk is paid for either way.
+    fn splay_to<Q>(&mut self, key: &Q) -> Link<K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut node = self.root?;
+        let found = loop {
+            let n = unsafe { node.as_ref() };
+            let next = match self.cmp.compare(n.key.borrow(), key) {
+                Ordering::Greater => n.left,
+                Ordering::Equal => break true,
+                Ordering::Less => n.right,
+            };
+            match next {
+                Some(child) => node = child,
+                None => break false,
+            }
+        };
+        unsafe { Node::splay(node) };
+        self.root = Some(node);
+        found.then_some(node)
+    }
+
+    /// Returns a double ended iterator over the entries, sorted by key.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            front: self.root.map(|r| unsafe { Node::leftmost(r) }),
+            back: self.root.map(|r| unsafe { Node::rightmost(r) }),
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a double ended iterator over the entries, sorted by key, with mutable
+    /// references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            front: self.root.map(|r| unsafe { Node::leftmost(r) }),
+            back: self.root.map(|r| unsafe { Node::rightmost(r) }),
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Walks the whole tree and checks key order, `parent` pointers and the length, reporting
+    /// the first violation.
+    pub fn validate(&self) -> Result<(), InvariantError<'_, K>>
+    where
+        C: Comparator<K>,
+    {
+        // Every node together with its expected parent and the keys it has to lie between.
+        let mut stack = Vec::from_iter(self.root.map(|r| (r, None, None, None)));
+        let mut count = 0;
+        while let Some((node, parent, low, high)) = stack.pop() {
+            count += 1;
+            let n = unsafe { node.as_ref() };
+            let key = &n.key;
+            if n.parent != parent {
+                return Err(InvariantError::ParentLink { key });
+            }
+            if low.is_some_and(|low| self.cmp.compare(low, key).is_ge())
+                || high.is_some_and(|high| self.cmp.compare(key, high).is_ge())
+            {
+                return Err(InvariantError::KeyOrder { key });
+            }
+            if let Some(r) = n.right {
+                stack.push((r, Some(node), Some(key), high));
+            }
+            if let Some(l) = n.left {
+                stack.push((l, Some(node), low, Some(key)));
+            }
+        }
+        if count != self.len {
+            return Err(InvariantError::Len {
+                expected: count,
+                found: self.len,
+            });
+        }
+        Ok(())
+    }
+
+    fn find<Q>(&self, key: &Q) -> Link<K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut node = self.root;
+        while let Some(n) = node.map(|n| unsafe { n.as_ref() }) {
+            match self.cmp.compare(n.key.borrow(), key) {
+                Ordering::Greater => node = n.left,
+                Ordering::Equal => return node,
+                Ordering::Less => node = n.right,
+            }
+        }
+        None
+    }
+}
+
+impl<K, V, C: Default> Default for SplayTree<K, V, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+impl<K, V, C: Comparator<K> + Default> FromIterator<(K, V)> for SplayTree<K, V, C> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree = Self::default();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<K, V, C: Comparator<K>> Extend<(K, V)> for SplayTree<K, V, C> {
+    /// Inserts the entries one by one, which takes O(1) per entry if they arrive sorted.
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K: Copy + 'a, V: Copy + 'a, C: Comparator<K>> Extend<(&'a K, &'a V)>
+    for SplayTree<K, V, C>
+{
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(k, v)| (*k, *v)));
+    }
+}
+
+impl<K: Clone, V: Clone, C: Comparator<K> + Clone> Clone for SplayTree<K, V, C> {
+    fn clone(&self) -> Self {
+        let mut tree = Self::with_comparator(self.cmp.clone());
+        tree.extend(self.iter().map(|(k, v)| (k.clone(), v.clone())));
+        tree
+    }
+}
+
+impl<K: PartialEq, V: PartialEq, C> PartialEq for SplayTree<K, V, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq, C> Eq for SplayTree<K, V, C> {}
+
+impl<K: PartialOrd, V: PartialOrd, C> PartialOrd for SplayTree<K, V, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, V: Ord, C> Ord for SplayTree<K, V, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Hash, V: Hash, C> Hash for SplayTree<K, V, C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        for entry in self {
+            entry.hash(state);
         }
     }
+}
 
-    pub fn contains(&self, needle: K) -> bool {
-        self.root
-            .map(|r| unsafe { r.as_ref().contains(needle) })
-            .unwrap_or(false)
+impl<K, Q, V, C> Index<&Q> for SplayTree<K, V, C>
+where
+    K: Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+{
+    type Output = V;
+
+    /// Panics if the key is not present in the tree.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
     }
 }
 
-impl<K: PartialOrd, V> Drop for SplayTree<K, V> {
+impl<'a, K, V, C> IntoIterator for &'a SplayTree<K, V, C> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a mut SplayTree<K, V, C> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, C> IntoIterator for SplayTree<K, V, C> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { tree: self }
+    }
+}
+
+impl<K, V, C> Drop for SplayTree<K, V, C> {
     fn drop(&mut self) {
-        if let Some(root) = self.root {
-            let root = unsafe { Box::from_raw(root.as_ptr()) };
-            root.drop_children();
+        unsafe { Node::drop_tree(self.root.take()) };
+    }
+}
+
+/// In order iterator over the entries of a [`SplayTree`].
+///
+/// Walks the tree along the `parent` pointers of the nodes and does not splay.
+pub struct Iter<'a, K, V> {
+    front: Link<K, V>,
+    back: Link<K, V>,
+    len: usize,
+    _marker: PhantomData<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let node = unsafe { self.front?.as_ref() };
+        self.front = unsafe { Node::successor(node.into()) };
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let node = unsafe { self.back?.as_ref() };
+        self.back = unsafe { Node::predecessor(node.into()) };
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Send for Iter<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Sync for Iter<'a, K, V> {}
+
+impl<'a, K, V> Clone for Iter<'a, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            front: self.front,
+            back: self.back,
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// In order iterator over the entries of a [`SplayTree`] with mutable references to the
+/// values.
+pub struct IterMut<'a, K, V> {
+    front: Link<K, V>,
+    back: Link<K, V>,
+    len: usize,
+    _marker: PhantomData<&'a mut Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let node = unsafe { &mut *self.front?.as_ptr() };
+        self.front = unsafe { Node::successor(node.into()) };
+        Some((&node.key, &mut node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
         }
+        self.len -= 1;
+        let node = unsafe { &mut *self.back?.as_ptr() };
+        self.back = unsafe { Node::predecessor(node.into()) };
+        Some((&node.key, &mut node.value))
     }
 }
 
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+impl<'a, K, V> FusedIterator for IterMut<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Send> Send for IterMut<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Sync for IterMut<'a, K, V> {}
+
+/// Owning in order iterator over the entries of a [`SplayTree`].
+///
+/// Every step splays the smallest or largest remaining node to the root and unlinks it.
+pub struct IntoIter<K, V, C = Natural> {
+    tree: SplayTree<K, V, C>,
+}
+
+impl<K, V, C> Iterator for IntoIter<K, V, C> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tree.pop_first()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.tree.len, Some(self.tree.len))
+    }
+}
+
+impl<K, V, C> DoubleEndedIterator for IntoIter<K, V, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.tree.pop_last()
+    }
+}
+
+impl<K, V, C> ExactSizeIterator for IntoIter<K, V, C> {}
+impl<K, V, C> FusedIterator for IntoIter<K, V, C> {}
+
 type Link<K, V> = Option<NonNull<Node<K, V>>>;
 type Parant<K, V> = Option<NonNull<Node<K, V>>>;
 
-pub struct Node<K: PartialOrd, V> {
+pub struct Node<K, V> {
     parent: Parant<K, V>,
     key: K,
     value: V,
@@ -78,7 +543,7 @@ pub struct Node<K: PartialOrd, V> {
     right: Link<K, V>,
 }
 
-impl<K: PartialOrd + Debug, V: Debug> Debug for Node<K, V> {
+impl<K: Debug, V: Debug> Debug for Node<K, V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Node")
             .field("key", &self.key)
@@ -90,7 +555,7 @@ impl<K: PartialOrd + Debug, V: Debug> Debug for Node<K, V> {
     }
 }
 
-impl<K: PartialOrd, V> Node<K, V> {
+impl<K, V> Node<K, V> {
     fn new(key: K, value: V) -> Box<Self> {
         Box::new(Self {
             parent: None,
@@ -101,32 +566,62 @@ impl<K: PartialOrd, V> Node<K, V> {
         })
     }
 
-    fn drop_children(mut self) {
-        if let Some(left) = self.left.take() {
-            let left = unsafe { Box::from_raw(left.as_ptr()) };
-            left.drop_children()
+    /// Frees every node of the tree below `link` without recursing.
+    unsafe fn drop_tree(mut link: Link<K, V>) {
+        while let Some(mut n) = link {
+            if let Some(mut l) = n.as_mut().left.take() {
+                // Rotate the left child up, so that the tree eventually degenerates into a right
+                // spine which can be freed from the top.
+                n.as_mut().left = l.as_mut().right.take();
+                l.as_mut().right = Some(n);
+                link = Some(l);
+            } else {
+                link = n.as_ref().right;
+                drop(Box::from_raw(n.as_ptr()));
+            }
         }
-        if let Some(right) = self.right {
-            let right = unsafe { Box::from_raw(right.as_ptr()) };
-            right.drop_children()
+    }
+
+    unsafe fn leftmost(mut node: NonNull<Self>) -> NonNull<Self> {
+        while let Some(l) = node.as_ref().left {
+            node = l;
+        }
+        node
+    }
+
+    unsafe fn rightmost(mut node: NonNull<Self>) -> NonNull<Self> {
+        while let Some(r) = node.as_ref().right {
+            node = r;
+        }
+        node
+    }
+
+    /// Returns the node following `node` in key order.
+    unsafe fn successor(mut node: NonNull<Self>) -> Link<K, V> {
+        if let Some(r) = node.as_ref().right {
+            return Some(Self::leftmost(r));
         }
+        while let Some(p) = node.as_ref().parent {
+            if p.as_ref().left == Some(node) {
+                return Some(p);
+            }
+            node = p;
+        }
+        None
     }
 
-    fn contains(&self, needle: K) -> bool {
-        match self.key.partial_cmp(&needle) {
-            Some(r) => match r {
-                Ordering::Greater => self
-                    .left
-                    .map(|l| unsafe { l.as_ref().contains(needle) })
-                    .unwrap_or(false),
-                Ordering::Equal => true,
-                Ordering::Less => self
-                    .right
-                    .map(|r| unsafe { r.as_ref().contains(needle) })
-                    .unwrap_or(false),
-            },
-            None => panic!("Failed to compare values"),
+    /// Returns the node preceding `node` in key order.
+    unsafe fn predecessor(mut node: NonNull<Self>) -> Link<K, V> {
+        if let Some(l) = node.as_ref().left {
+            return Some(Self::rightmost(l));
+        }
+        while let Some(p) = node.as_ref().parent {
+            if p.as_ref().right == Some(node) {
+                return Some(p);
+            }
+            node = p;
         }
+        None
     }
 
     fn new_with_parent(key: K, value: V, parent: Parant<K, V>) -> Box<Self> {
@@ -145,7 +640,7 @@ impl<K: PartialOrd, V> Node<K, V> {
         };
         self.left = new_parent.as_mut().right.take();
         if let Some(p) = self.parent.map(|mut p| p.as_mut()) {
-            if p.left.map(|l| l.as_ref().key == self.key).unwrap_or(false) {
+            if p.left == NonNull::new(self as *mut Self) {
                 p.left = Some(new_parent);
             } else {
                 p.right = Some(new_parent);
@@ -165,7 +660,7 @@ impl<K: PartialOrd, V> Node<K, V> {
         };
         self.right = new_parent.as_mut().left.take();
         if let Some(p) = self.parent.map(|mut p| p.as_mut()) {
-            if p.left.map(|l| l.as_ref().key == self.key).unwrap_or(false) {
+            if p.left == NonNull::new(self as *mut Self) {
                 p.left = Some(new_parent);
             } else {
                 p.right = Some(new_parent);
@@ -179,68 +674,32 @@ impl<K: PartialOrd, V> Node<K, V> {
         new_parent.as_mut().left = NonNull::new(self as *mut Self);
     }
 
-    unsafe fn insert(mut dst: NonNull<Node<K, V>>, key: K, mut value: V) {
-        let target = dst.as_mut();
-        match target.key.partial_cmp(&key) {
-            Some(Ordering::Equal) => std::mem::swap(&mut target.value, &mut value),
-            Some(Ordering::Greater) => match target.left.as_ref() {
-                Some(l) => {
-                    Self::insert(*l, key, value);
-                }
-                None => {
-                    let new_element = Node::new_with_parent(key, value, Some(dst));
-                    target.left = NonNull::new(Box::into_raw(new_element));
-                }
-            },
-            Some(Ordering::Less) => match target.right.as_ref() {
-                Some(r) => {
-                    Self::insert(*r, key, value);
-                }
-                None => {
-                    let new_element = Node::new_with_parent(key, value, Some(dst));
-                    target.right = NonNull::new(Box::into_raw(new_element));
-                }
-            },
-            None => panic!("Failed to compare"),
+    /// Lifts `child` above `node`, which has to be its parent.
+    unsafe fn rotate_up(mut node: NonNull<Self>, child: NonNull<Self>) {
+        if node.as_ref().left == Some(child) {
+            node.as_mut().rotate_right();
+        } else {
+            node.as_mut().rotate_left();
         }
     }
 
-    unsafe fn remove(mut node: NonNull<Node<K, V>>, key: K) -> Option<V> {
-        while node.as_ref().key != key {
-            match node
-                .as_ref()
-                .key
-                .partial_cmp(&key)
-                .expect("to be able to compare keys")
-            {
-                Ordering::Greater => {
-                    if let Some(l) = node.as_ref().left {
-                        node = l;
-                    } else {
-                        return None;
-                    }
-                }
-                Ordering::Less => {
-                    if let Some(r) = node.as_ref().right {
-                        node = r;
+    /// Moves `node` to the root of its tree through zig, zig-zig and zig-zag steps.
+    unsafe fn splay(node: NonNull<Self>) {
+        while let Some(parent) = node.as_ref().parent {
+            match parent.as_ref().parent {
+                None => Self::rotate_up(parent, node),
+                Some(grandparent) => {
+                    let node_left = parent.as_ref().left == Some(node);
+                    let parent_left = grandparent.as_ref().left == Some(parent);
+                    if node_left == parent_left {
+                        Self::rotate_up(grandparent, parent);
+                        Self::rotate_up(parent, node);
                     } else {
-                        return None;
+                        Self::rotate_up(parent, node);
+                        Self::rotate_up(grandparent, node);
                     }
                 }
-                Ordering::Equal => {}
             }
         }
-        let mut parent = node.as_ref().parent.unwrap();
-        if parent
-            .as_ref()
-            .left
-            .map(|l| l.as_ref().key == key)
-            .unwrap_or(false)
-        {
-            let d = Box::from_raw(parent.as_mut().left.take().unwrap().as_ptr());
-            return Some(d.value);
-        }
-        let d = Box::from_raw(parent.as_mut().right.take().unwrap().as_ptr());
-        return Some(d.value);
     }
 }
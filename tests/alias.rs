@@ -1,4 +1,6 @@
-use infra_bits::rand::{Alias, EventEmmiter};
+use infra_bits::rand::{Alias, Emitter, EventEmmiter, Leaf};
+
+type Event = Box<dyn Fn(&mut [usize])>;
 
 #[test]
 #[should_panic]
@@ -14,7 +16,7 @@ fn test_generate() {
 
 #[test]
 fn test_generate_event() {
-    let alias: EventEmmiter<3, Box<dyn Fn(&mut [usize])>> = EventEmmiter::new(
+    let alias: EventEmmiter<3, Event> = EventEmmiter::new(
         &[0.2, 0.3, 0.5],
         [
             Box::new(event_one),
@@ -42,3 +44,86 @@ fn event_two(s: &mut [usize]) {
 fn event_three(s: &mut [usize]) {
     s[2] += 1;
 }
+
+#[derive(Debug, PartialEq)]
+enum Fault {
+    DiskFull,
+    DiskSlow,
+    NetDown,
+    NetLoss,
+    NetJitter,
+    Oom,
+}
+
+fn fault_tree() -> EventEmmiter<3, Box<dyn Emitter<Leaf = Fault>>> {
+    let disk: EventEmmiter<2, Leaf<Fault>> = EventEmmiter::new(
+        &[0.25, 0.75],
+        [Leaf(Fault::DiskFull), Leaf(Fault::DiskSlow)],
+    );
+    let net: EventEmmiter<3, Leaf<Fault>> = EventEmmiter::new(
+        &[0.5, 0.3, 0.2],
+        [
+            Leaf(Fault::NetDown),
+            Leaf(Fault::NetLoss),
+            Leaf(Fault::NetJitter),
+        ],
+    );
+    EventEmmiter::new(
+        &[0.4, 0.5, 0.1],
+        [Box::new(disk), Box::new(net), Box::new(Leaf(Fault::Oom))],
+    )
+}
+
+#[test]
+fn test_generate_leaf_path() {
+    let tree = fault_tree();
+    for _ in 0..1000 {
+        let (leaf, path) = tree.generate_leaf();
+        let expected = match path.as_slice() {
+            [0, 0] => Fault::DiskFull,
+            [0, 1] => Fault::DiskSlow,
+            [1, 0] => Fault::NetDown,
+            [1, 1] => Fault::NetLoss,
+            [1, 2] => Fault::NetJitter,
+            [2] => Fault::Oom,
+            p => panic!("unexpected path {:?}", p),
+        };
+        assert_eq!(*leaf, expected);
+    }
+}
+
+#[test]
+fn test_leaf_probabilities() {
+    let tree = fault_tree();
+    let leaves = tree.leaf_probabilities();
+    assert_eq!(leaves.len(), 6);
+    let expected = [0.1, 0.3, 0.25, 0.15, 0.1, 0.1];
+    for ((_, _, p), e) in leaves.iter().zip(expected) {
+        assert!((p - e).abs() < 1e-12);
+    }
+    assert_eq!(leaves[5].0, vec![2]);
+    assert_eq!(*leaves[5].1, Fault::Oom);
+}
+
+#[test]
+fn test_flatten() {
+    let tree = fault_tree();
+    let flat = tree.flatten::<6>();
+    let leaves = tree.leaf_probabilities();
+    let mut counts = [0usize; 6];
+    let n = 1000000;
+    for _ in 0..n {
+        let event = flat.generate();
+        let i = leaves.iter().position(|(_, l, _)| l == event).unwrap();
+        counts[i] += 1;
+    }
+    for ((_, _, p), c) in leaves.iter().zip(counts) {
+        assert!((c as f64 / n as f64 - p).abs() < 0.01);
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_flatten_wrong_size() {
+    fault_tree().flatten::<5>();
+}
@@ -0,0 +1,189 @@
+use infra_bits::{
+    compare::{Natural, Reverse},
+    rand::{arena::Entry, ArenaTreeMap},
+};
+use std::{collections::BTreeMap, ops::Bound};
+
+#[test]
+fn test_matches_btree_map() {
+    let mut arena = ArenaTreeMap::with_capacity(64);
+    let mut expected = BTreeMap::new();
+    for i in 0..2000u64 {
+        let key = (i * 7919) % 503;
+        if i % 3 == 0 {
+            assert_eq!(arena.remove(&key), expected.remove(&key));
+        } else {
+            assert_eq!(arena.insert(key, i), expected.insert(key, i));
+        }
+    }
+    assert_eq!(arena.len(), expected.len());
+    assert!(arena.iter().eq(expected.iter()));
+    assert!(arena.iter().rev().eq(expected.iter().rev()));
+    assert!(arena.range(100..200).eq(expected.range(100..200)));
+    for (i, key) in expected.keys().enumerate() {
+        assert_eq!(arena.nth(i).map(|(k, _)| k), Some(key));
+        assert_eq!(arena.rank(key), i);
+        assert_eq!(arena.get(key), expected.get(key));
+    }
+}
+
+#[test]
+fn test_reuses_freed_slots() {
+    let mut arena = ArenaTreeMap::new();
+    for i in 0..100 {
+        arena.insert(i, ());
+    }
+    let capacity = arena.capacity();
+    for _ in 0..10 {
+        for i in 0..100 {
+            arena.remove(&i);
+        }
+        for i in 100..200 {
+            arena.insert(i, ());
+        }
+        for i in 100..200 {
+            arena.remove(&i);
+        }
+        for i in 0..100 {
+            arena.insert(i, ());
+        }
+    }
+    assert_eq!(arena.capacity(), capacity);
+}
+
+#[test]
+fn test_shrink_to_fit() {
+    let mut arena = ArenaTreeMap::new();
+    for i in 0..1000 {
+        arena.insert(i, i * 2);
+    }
+    for i in (0..1000).filter(|i| i % 10 != 0) {
+        arena.remove(&i);
+    }
+    arena.shrink_to_fit();
+    assert_eq!(arena.len(), 100);
+    assert!(arena.capacity() < 1000);
+    assert!(arena.keys().copied().eq((0..1000).step_by(10)));
+    for i in (0..1000).step_by(10) {
+        assert_eq!(arena.get(&i), Some(&(i * 2)));
+    }
+    arena.insert(5, 10);
+    assert_eq!(arena.rank(&10), 2);
+}
+
+#[test]
+fn test_entry_and_mutable_iteration() {
+    let mut arena = ArenaTreeMap::with_capacity_and_comparator(16, Reverse(Natural));
+    assert!(arena.capacity() >= 16);
+    for key in [5, 1, 4, 2, 3] {
+        *arena.entry(key).or_insert(0) += key;
+    }
+    *arena.entry(4).and_modify(|v| *v *= 10).or_default() += 1;
+    assert_eq!(arena.get(&4), Some(&41));
+    match arena.entry(2) {
+        Entry::Occupied(o) => assert_eq!(o.remove_entry(), (2, 2)),
+        Entry::Vacant(_) => unreachable!(),
+    }
+    assert_eq!(arena.validate(), Ok(()));
+
+    // Mutable iteration follows the order of the comparator, not the order of the slots.
+    let mut seen = Vec::new();
+    for (value, new) in arena.values_mut().zip([100, 200, 300, 400]) {
+        seen.push(*value);
+        *value = new;
+    }
+    assert_eq!(seen, [5, 41, 3, 1]);
+    assert!(arena
+        .iter()
+        .eq([(&5, &100), (&4, &200), (&3, &300), (&1, &400)]));
+    for (_, v) in arena.range_mut((Bound::Included(4), Bound::Included(3))) {
+        *v += 1;
+    }
+    for (_, v) in &mut arena {
+        *v *= 2;
+    }
+    assert!(arena.values().copied().eq([200, 402, 602, 800]));
+    assert_eq!(arena.iter_mut().next_back(), Some((&1, &mut 800)));
+
+    let mut iter = arena.into_iter();
+    assert_eq!(iter.next(), Some((5, 200)));
+    assert_eq!(iter.next_back(), Some((1, 800)));
+    assert_eq!(iter.len(), 2);
+    assert!(iter.eq([(4, 402), (3, 602)]));
+
+    fn send_sync<T: Send + Sync>() {}
+    send_sync::<ArenaTreeMap<String, u32>>();
+}
+
+#[test]
+fn test_split_join_and_union() {
+    let mut arena: ArenaTreeMap<u32, u32> = ArenaTreeMap::new();
+    for i in 0..1000 {
+        arena.insert(i, i);
+    }
+    let upper = arena.split_off(&600);
+    assert_eq!(arena.validate(), Ok(()));
+    assert_eq!(upper.validate(), Ok(()));
+    assert!(arena.keys().copied().eq(0..600));
+    assert!(upper.keys().copied().eq(600..1000));
+
+    let joined = ArenaTreeMap::join(arena, upper);
+    assert_eq!(joined.validate(), Ok(()));
+    assert!(joined
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq((0..1000).map(|i| (i, i))));
+
+    let mut other = ArenaTreeMap::new();
+    for i in (500..1500).step_by(2) {
+        other.insert(i, 0);
+    }
+    let mut expected: BTreeMap<_, _> = joined.iter().map(|(k, v)| (*k, *v)).collect();
+    expected.extend(other.iter().map(|(k, v)| (*k, *v)));
+    let mut merged = joined.union(other);
+    assert_eq!(merged.validate(), Ok(()));
+    assert!(merged.iter().eq(expected.iter()));
+
+    let mut rest = merged.split_off(&0);
+    assert!(merged.is_empty());
+    merged.append(&mut rest);
+    assert!(rest.is_empty());
+    assert_eq!(merged.validate(), Ok(()));
+    assert_eq!(merged.len(), expected.len());
+}
+
+#[test]
+#[should_panic = "left map overlaps the right map"]
+fn test_join_overlapping() {
+    let left: ArenaTreeMap<_, _> = [(1, ()), (5, ())].into_iter().collect();
+    let right: ArenaTreeMap<_, _> = [(3, ())].into_iter().collect();
+    ArenaTreeMap::join(left, right);
+}
+
+#[test]
+fn test_join_moves_the_smaller_map() {
+    for (split, len) in [(10, 1000), (990, 1000), (0, 5), (5, 5)] {
+        let mut left: ArenaTreeMap<_, _> = (0..len).map(|k| (k, k * 2)).collect();
+        let right = left.split_off(&split);
+        let joined = ArenaTreeMap::join(left, right);
+        assert_eq!(joined.validate(), Ok(()));
+        assert!(joined
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .eq((0..len).map(|k| (k, k * 2))));
+    }
+}
+
+#[test]
+#[should_panic = "range start is greater than range end"]
+fn test_range_inverted() {
+    let map: ArenaTreeMap<_, _> = [(1, ()), (5, ())].into_iter().collect();
+    let _ = map.range((Bound::Included(4), Bound::Included(2)));
+}
+
+#[test]
+#[should_panic = "range start and end are equal and excluded"]
+fn test_range_mut_excluded_twice() {
+    let mut map: ArenaTreeMap<_, _> = [(1, ()), (5, ())].into_iter().collect();
+    let _ = map.range_mut((Bound::Excluded(3), Bound::Excluded(3)));
+}
@@ -0,0 +1,162 @@
+use infra_bits::{
+    monoid::{Max, Min, Sum},
+    rand::AugmentedTreeMap,
+};
+use std::{collections::BTreeMap, ops::Bound};
+
+#[test]
+fn test_sum_matches_btree_map() {
+    let mut map = AugmentedTreeMap::new(Sum);
+    let mut expected = BTreeMap::new();
+    for i in 0..2000i64 {
+        let key = (i * 7919) % 503;
+        if i % 3 == 0 {
+            assert_eq!(map.remove(&key), expected.remove(&key));
+        } else {
+            assert_eq!(map.insert(key, i), expected.insert(key, i));
+        }
+    }
+    assert_eq!(map.len(), expected.len());
+    assert!(map.iter().eq(expected.iter()));
+    assert_eq!(map.total(), expected.values().sum::<i64>());
+    for (a, b) in [
+        (0, 503),
+        (17, 18),
+        (100, 250),
+        (-5, 40),
+        (300, 299),
+        (502, 1000),
+    ] {
+        let sum = expected.range(a..b.max(a)).map(|(_, v)| v).sum::<i64>();
+        assert_eq!(map.aggregate(a..b.max(a)), sum, "range {a}..{b}");
+    }
+    let sum = expected.range(50..=60).map(|(_, v)| v).sum::<i64>();
+    assert_eq!(map.aggregate(50..=60), sum);
+    let sum = expected.range(..100).map(|(_, v)| v).sum::<i64>();
+    assert_eq!(map.aggregate(..100), sum);
+    let range = (Bound::Excluded(10), Bound::Unbounded);
+    let sum = expected.range(range).map(|(_, v)| v).sum::<i64>();
+    assert_eq!(map.aggregate(range), sum);
+}
+
+#[test]
+fn test_min_max() {
+    let mut min = AugmentedTreeMap::new(Min);
+    let mut max = AugmentedTreeMap::new(Max);
+    for (k, v) in [(1, 5), (2, 3), (3, 8), (4, 1), (5, 9)] {
+        min.insert(k, v);
+        max.insert(k, v);
+    }
+    assert_eq!(min.aggregate(1..4), Some(3));
+    assert_eq!(max.aggregate(1..4), Some(8));
+    assert_eq!(min.aggregate(10..20), None);
+    assert_eq!(min.total(), Some(1));
+    min.remove(&4);
+    max.insert(5, 0);
+    assert_eq!(min.total(), Some(3));
+    assert_eq!(max.total(), Some(8));
+
+    let handle = std::thread::spawn(move || max.total());
+    assert_eq!(handle.join().unwrap(), Some(8));
+}
+
+#[test]
+fn test_choose_weighted() {
+    use infra_bits::monoid::Weight;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut map = AugmentedTreeMap::new(Weight(|job: &(&str, f64)| job.1));
+    assert_eq!(map.choose_weighted(&mut rng), None);
+    for (id, weight) in [(1, 1.0), (2, 2.0), (3, 0.0), (4, 4.0), (5, 100.0)] {
+        map.insert(id, ("job", weight));
+    }
+    map.remove(&5);
+    let mut counts = [0; 5];
+    for _ in 0..70_000 {
+        counts[*map.choose_weighted(&mut rng).unwrap().0] += 1;
+    }
+    assert_eq!(counts[3], 0);
+    assert_eq!(counts[0], 0);
+    for (id, expected) in [(1, 10_000), (2, 20_000), (4, 40_000)] {
+        let range = expected * 9 / 10..expected * 11 / 10;
+        assert!(range.contains(&counts[id]), "{counts:?}");
+    }
+
+    let mut counts = AugmentedTreeMap::new(Sum);
+    counts.insert("a", 0u64);
+    assert_eq!(counts.choose_weighted(&mut rng), None);
+    counts.insert("b", 3);
+    assert_eq!(counts.choose_weighted(&mut rng), Some((&"b", &3)));
+}
+
+#[test]
+fn test_split_join_and_set_operations() {
+    let build = |range: std::ops::Range<i64>, step: usize| -> AugmentedTreeMap<i64, i64, Sum> {
+        let mut map = AugmentedTreeMap::new(Sum);
+        for k in range.step_by(step) {
+            map.insert(k, k);
+        }
+        map
+    };
+    let mut map = build(0..1000, 1);
+    let upper = map.split_off(&400);
+    assert_eq!((map.len(), upper.len()), (400, 600));
+    assert_eq!(map.total(), (0..400).sum::<i64>());
+    assert_eq!(upper.first_key_value(), Some((&400, &400)));
+    let map = AugmentedTreeMap::join(map, upper);
+    assert_eq!(map.total(), (0..1000).sum::<i64>());
+
+    let evens = || build(0..1000, 2);
+    let threes = || build(0..1000, 3);
+    let union = evens().union(threes());
+    assert!(union
+        .iter()
+        .map(|(k, _)| *k)
+        .eq((0..1000).filter(|k| k % 2 == 0 || k % 3 == 0)));
+    assert_eq!(union.len(), union.iter().count());
+    let both = evens().intersection(threes());
+    assert!(both.iter().map(|(k, _)| *k).eq((0..1000).step_by(6)));
+    assert_eq!(both.total(), (0..1000).step_by(6).sum::<i64>());
+    let only = evens().difference(threes());
+    assert_eq!(
+        only.len(),
+        (0..1000).filter(|k| k % 2 == 0 && k % 3 != 0).count()
+    );
+    let either = evens().symmetric_difference(threes());
+    assert_eq!(
+        either.total(),
+        (0..1000)
+            .filter(|k| (k % 2 == 0) != (k % 3 == 0))
+            .sum::<i64>()
+    );
+
+    assert_eq!(either.floor(&6), Some((&4, &4)));
+    assert_eq!(either.ceiling(&6), Some((&8, &8)));
+    assert_eq!(either.predecessor(&4), Some((&3, &3)));
+    assert_eq!(either.successor(&4), Some((&8, &8)));
+}
+
+#[test]
+fn test_choose_weighted_skips_trailing_zero_weight() {
+    use rand::{
+        rngs::{mock::StepRng, StdRng},
+        Rng, SeedableRng,
+    };
+
+    let mut weights = StdRng::seed_from_u64(47);
+    for _ in 0..200 {
+        let mut map = AugmentedTreeMap::new(Sum);
+        for k in 0..1000 {
+            map.insert(k, weights.gen_range(0.0..1.0f64));
+        }
+        map.insert(1000, 0.0);
+        // Always draws the largest point below the total, which the prefixes can round under.
+        let mut rng = StepRng::new(u64::MAX, 0);
+        let (key, weight) = map.choose_weighted(&mut rng).unwrap();
+        assert!(
+            *key < 1000 && *weight > 0.0,
+            "chose {key} with weight {weight}"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use infra_bits::rand::IntervalMap;
+use std::ops::Range;
+
+fn intervals() -> Vec<Range<u32>> {
+    (0..500u32)
+        .map(|i| {
+            let start = (i * 7919) % 1000;
+            start..start + 1 + (i * 31) % 50
+        })
+        .collect()
+}
+
+#[test]
+fn test_queries_match_brute_force() {
+    let mut map = IntervalMap::new();
+    let mut expected: Vec<(Range<u32>, usize)> = vec![];
+    for (i, interval) in intervals().into_iter().enumerate() {
+        let old = expected.iter().position(|(r, _)| *r == interval);
+        assert_eq!(map.insert(interval.clone(), i), old.map(|p| expected[p].1));
+        match old {
+            Some(p) => expected[p].1 = i,
+            None => expected.push((interval, i)),
+        }
+    }
+    for (interval, _) in expected.clone().iter().step_by(3) {
+        let p = expected.iter().position(|(r, _)| r == interval).unwrap();
+        assert_eq!(map.remove(interval), Some(expected.remove(p).1));
+    }
+    assert_eq!(map.remove(&(2000..2001)), None);
+    assert_eq!(map.len(), expected.len());
+    expected.sort_by_key(|(r, _)| (r.start, r.end));
+    assert!(map.iter().eq(expected.iter().map(|(r, v)| (r, v))));
+
+    for point in (0..1100).step_by(7) {
+        let stabbed: Vec<_> = expected
+            .iter()
+            .filter(|(r, _)| r.contains(&point))
+            .map(|(r, v)| (r, v))
+            .collect();
+        assert_eq!(
+            map.stab(&point).collect::<Vec<_>>(),
+            stabbed,
+            "point {point}"
+        );
+    }
+    for query in [0..10, 100..101, 495..620, 990..2000] {
+        let overlapping: Vec<_> = expected
+            .iter()
+            .filter(|(r, _)| r.start < query.end && query.start < r.end)
+            .map(|(r, v)| (r, v))
+            .collect();
+        assert_eq!(map.overlapping(&query).collect::<Vec<_>>(), overlapping);
+    }
+}
+
+#[test]
+fn test_port_ranges() {
+    let map: IntervalMap<u16, &str> = [
+        (0..1024, "system"),
+        (1024..49152, "user"),
+        (8000..8100, "http"),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(
+        map.stab(&80).map(|(_, v)| *v).collect::<Vec<_>>(),
+        ["system"]
+    );
+    assert_eq!(
+        map.stab(&8080).map(|(_, v)| *v).collect::<Vec<_>>(),
+        ["user", "http"]
+    );
+    assert_eq!(
+        map.stab(&1024).map(|(_, v)| *v).collect::<Vec<_>>(),
+        ["user"]
+    );
+    assert_eq!(map.stab(&60000).count(), 0);
+    assert_eq!(map.get(&(8000..8100)), Some(&"http"));
+
+    let handle = std::thread::spawn(move || map.stab(&8050).count());
+    assert_eq!(handle.join().unwrap(), 2);
+}
+
+#[test]
+fn test_empty_and_inverted_queries() {
+    let mut map = IntervalMap::new();
+    map.insert(0..10, "a");
+    map.insert(4..6, "b");
+    assert_eq!(map.overlapping(&(5..5)).count(), 0);
+    #[allow(clippy::reversed_empty_ranges)]
+    let inverted = 7..3;
+    assert_eq!(map.overlapping(&inverted).count(), 0);
+    assert_eq!(map.overlapping(&(5..6)).count(), 2);
+    assert_eq!(map.stab(&5).count(), 2);
+}
@@ -0,0 +1,82 @@
+use infra_bits::rand::PersistentTreeMap;
+use std::{collections::BTreeMap, thread};
+
+#[test]
+fn test_matches_btree_map() {
+    let mut map = PersistentTreeMap::new();
+    let mut expected = BTreeMap::new();
+    for i in 0..2000u64 {
+        let key = (i * 7919) % 503;
+        if i % 3 == 0 {
+            expected.remove(&key);
+            map = map.remove(&key);
+        } else {
+            expected.insert(key, i);
+            map = map.insert(key, i);
+        }
+    }
+    assert_eq!(map.len(), expected.len());
+    assert!(map.iter().eq(expected.iter()));
+    for (key, value) in &expected {
+        assert_eq!(map.get(key), Some(value));
+    }
+    assert!(!map.contains(&1000));
+}
+
+#[test]
+fn test_old_versions_stay_valid() {
+    let versions: Vec<PersistentTreeMap<i32, i32>> = (0..100)
+        .scan(PersistentTreeMap::new(), |map, i| {
+            *map = map.insert(i, i);
+            Some(map.clone())
+        })
+        .collect();
+    for (i, version) in versions.iter().enumerate() {
+        assert_eq!(version.len(), i + 1);
+        assert!(version.iter().map(|(k, _)| *k).eq(0..=i as i32));
+    }
+
+    let last = versions.last().unwrap();
+    let removed = last.remove(&50).insert(0, -1);
+    assert_eq!(last.get(&50), Some(&50));
+    assert_eq!(last.get(&0), Some(&0));
+    assert_eq!(removed.get(&50), None);
+    assert_eq!(removed.get(&0), Some(&-1));
+    assert_eq!(removed.len(), 99);
+}
+
+#[test]
+fn test_snapshots_across_threads() {
+    let map: PersistentTreeMap<_, _> = (0..1000).map(|i| (i, i * 2)).collect();
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let snapshot = map.clone();
+            thread::spawn(move || snapshot.iter().map(|(_, v)| v).sum::<i32>())
+        })
+        .collect();
+    let map = map.remove(&0).insert(1000, 0);
+    for reader in readers {
+        assert_eq!(reader.join().unwrap(), 999 * 1000);
+    }
+    assert_eq!(map.len(), 1000);
+}
+
+#[test]
+fn test_replace_existing_keys() {
+    let original: PersistentTreeMap<_, _> = (0..500).map(|i| (i, i)).collect();
+    let mut map = original.clone();
+    for round in 1..=3 {
+        for i in (0..500).rev() {
+            map = map.insert(i, i * 10 + round);
+        }
+        assert_eq!(map.len(), 500);
+        assert!(map
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .eq((0..500).map(|i| (i, i * 10 + round))));
+    }
+    assert!(original
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq((0..500).map(|i| (i, i))));
+}
@@ -0,0 +1,130 @@
+use infra_bits::rand::RMultiSet;
+
+#[test]
+fn test_counts() {
+    let mut set = RMultiSet::new();
+    for t in [30, 10, 20, 10, 30, 10] {
+        set.insert(t);
+    }
+    assert_eq!(set.len(), 6);
+    assert_eq!(set.distinct_len(), 3);
+    assert_eq!(set.count(&10), 3);
+    assert_eq!(set.count(&15), 0);
+    assert_eq!(set.insert_many(20, 2), 3);
+
+    assert!(set.remove_one(&10));
+    assert_eq!(set.count(&10), 2);
+    assert_eq!(set.remove_all(&30), 2);
+    assert!(!set.remove_one(&30));
+    assert!(!set.contains(&30));
+    assert_eq!(set.remove_all(&30), 0);
+    assert_eq!(set.len(), 5);
+    assert!(set.iter().eq(&[10, 10, 20, 20, 20]));
+    assert!(set.counts().eq([(&10, 2), (&20, 3)]));
+
+    set.remove_one(&10);
+    set.remove_one(&10);
+    assert_eq!(set.distinct_len(), 1);
+    assert_eq!(format!("{set:?}"), "[20, 20, 20]");
+}
+
+#[test]
+fn test_order_statistics_with_multiplicity() {
+    let keys: Vec<u32> = (0..1000).map(|i| (i * 7919) % 97).collect();
+    let set: RMultiSet<u32> = keys.iter().copied().collect();
+    let mut sorted = keys.clone();
+    sorted.sort();
+    assert_eq!(set.len(), sorted.len());
+    assert!(set.iter().eq(sorted.iter()));
+    for (i, key) in sorted.iter().enumerate() {
+        assert_eq!(set.nth(i), Some(key));
+    }
+    assert_eq!(set.nth(sorted.len()), None);
+    for key in 0..100 {
+        assert_eq!(set.rank(&key), sorted.partition_point(|k| *k < key));
+    }
+}
+
+#[test]
+fn test_neighbours_split_and_join() {
+    let mut set: RMultiSet<u32> = [5, 1, 5, 9, 3, 9, 9].into_iter().collect();
+    assert_eq!((set.first(), set.last()), (Some(&1), Some(&9)));
+    assert_eq!(set.floor(&4), Some(&3));
+    assert_eq!(set.ceiling(&5), Some(&5));
+    assert_eq!(set.predecessor(&5), Some(&3));
+    assert_eq!(set.successor(&5), Some(&9));
+    assert_eq!(set.successor(&9), None);
+
+    let upper = set.split_off(&5);
+    assert_eq!((set.len(), upper.len()), (2, 5));
+    assert_eq!(upper.nth(2), Some(&9));
+    let set = RMultiSet::join(set, upper);
+    assert_eq!(set.len(), 7);
+    assert_eq!(set.rank(&9), 4);
+}
+
+#[test]
+fn test_multiset_algebra() {
+    let build = |keys: &[u32]| keys.iter().copied().collect::<RMultiSet<u32>>();
+    let counts = |set: &RMultiSet<u32>| set.counts().map(|(k, c)| (*k, c)).collect::<Vec<_>>();
+    let a = || build(&[1, 1, 1, 2, 3, 3]);
+    let b = || build(&[1, 3, 3, 3, 4]);
+
+    assert_eq!(
+        counts(&a().into_union(b())),
+        [(1, 3), (2, 1), (3, 3), (4, 1)]
+    );
+    assert_eq!(counts(&a().into_sum(b())), [(1, 4), (2, 1), (3, 5), (4, 1)]);
+    assert_eq!(counts(&a().into_intersection(b())), [(1, 1), (3, 2)]);
+    assert_eq!(counts(&a().into_difference(b())), [(1, 2), (2, 1)]);
+    assert_eq!(
+        counts(&a().into_symmetric_difference(b())),
+        [(1, 2), (2, 1), (3, 1), (4, 1)]
+    );
+    let sum = a().into_sum(b());
+    assert_eq!(sum.len(), 11);
+    assert_eq!(sum.nth(9), Some(&3));
+    assert!(a().is_subset(&sum));
+    assert!(sum.is_superset(&b()));
+    assert!(!a().is_subset(&b()));
+
+    let mut c = a();
+    let mut d = b();
+    c.append(&mut d);
+    assert!(d.is_empty());
+    assert_eq!(counts(&c), counts(&sum));
+}
+
+#[test]
+#[should_panic(expected = "multiset count overflow")]
+fn test_insert_many_overflow() {
+    let mut set = RMultiSet::new();
+    set.insert(1);
+    set.insert_many(1, usize::MAX);
+}
+
+#[test]
+fn test_count_overflow_leaves_set_intact() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut set = RMultiSet::new();
+    set.insert_many(1, usize::MAX - 1);
+    let result = catch_unwind(AssertUnwindSafe(|| set.insert_many(2, 2)));
+    assert!(result.is_err());
+    assert_eq!((set.count(&1), set.count(&2)), (usize::MAX - 1, 0));
+
+    let mut other = RMultiSet::new();
+    other.insert_many(1, 2);
+    let result = catch_unwind(AssertUnwindSafe(|| set.append(&mut other)));
+    assert!(result.is_err());
+    assert_eq!((set.count(&1), other.count(&1)), (usize::MAX - 1, 2));
+
+    let mut large = RMultiSet::new();
+    large.insert_many(2, usize::MAX);
+    let result = catch_unwind(|| {
+        let mut a = RMultiSet::new();
+        a.insert_many(1, usize::MAX);
+        a.into_union(large)
+    });
+    assert!(result.is_err());
+}
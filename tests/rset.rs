@@ -0,0 +1,127 @@
+use infra_bits::rand::RSet;
+
+#[test]
+fn test_order_statistics() {
+    let mut set = RSet::new();
+    for i in [50, 10, 40, 20, 30] {
+        set.insert(i);
+    }
+    set.remove(&40);
+    assert_eq!(set.nth(0), Some(&10));
+    assert_eq!(set.nth(3), Some(&50));
+    assert_eq!(set.nth(4), None);
+    assert_eq!(set.rank(&30), 2);
+    assert_eq!(set.rank(&35), 3);
+}
+
+#[test]
+fn test_neighbour_queries() {
+    let mut set = RSet::new();
+    for i in [3, 9, 6] {
+        set.insert(i);
+    }
+    assert_eq!(set.floor(&8), Some(&6));
+    assert_eq!(set.ceiling(&7), Some(&9));
+    assert_eq!(set.predecessor(&3), None);
+    assert_eq!(set.successor(&3), Some(&6));
+    assert_eq!(set.first(), Some(&3));
+    assert_eq!(set.last(), Some(&9));
+    assert_eq!(set.pop_first(), Some(3));
+    assert_eq!(set.pop_last(), Some(9));
+    assert_eq!(set.first(), Some(&6));
+}
+
+#[test]
+fn test_basic_api() {
+    let mut set: RSet<_> = [5, 1, 3].into_iter().collect();
+    assert!(set.insert(4));
+    assert!(!set.insert(4));
+    assert!(set.contains(&3));
+    assert!(set.remove(&3));
+    assert!(!set.remove(&3));
+    assert!(!set.contains(&3));
+    assert_eq!(set.len(), 3);
+    assert!(set.iter().eq(&[1, 4, 5]));
+    assert!(set.iter().rev().eq(&[5, 4, 1]));
+    assert!(set.range(2..5).eq(&[4]));
+    set.extend([0, 9]);
+    assert_eq!(set.into_iter().collect::<Vec<_>>(), [0, 1, 4, 5, 9]);
+    assert!(RSet::<i32>::new().is_empty());
+}
+
+#[test]
+fn test_set_algebra() {
+    let a: RSet<u32> = (0..300).filter(|i| i % 2 == 0).collect();
+    let b: RSet<u32> = (0..300).filter(|i| i % 3 == 0).collect();
+    let union: Vec<_> = (0..300).filter(|i| i % 2 == 0 || i % 3 == 0).collect();
+    let intersection: Vec<_> = (0..300).filter(|i| i % 6 == 0).collect();
+    let difference: Vec<_> = (0..300).filter(|i| i % 2 == 0 && i % 3 != 0).collect();
+    let symmetric: Vec<_> = (0..300).filter(|i| (i % 2 == 0) != (i % 3 == 0)).collect();
+
+    assert!(a.union(&b).eq(&union));
+    assert!(a.intersection(&b).eq(&intersection));
+    assert!(a.difference(&b).eq(&difference));
+    assert!(a.symmetric_difference(&b).eq(&symmetric));
+
+    let copy = |s: &RSet<u32>| s.iter().copied().collect::<RSet<u32>>();
+    assert!(copy(&a).into_union(copy(&b)).iter().eq(&union));
+    assert!(copy(&a)
+        .into_intersection(copy(&b))
+        .iter()
+        .eq(&intersection));
+    assert!(copy(&b)
+        .into_intersection(copy(&a))
+        .iter()
+        .eq(&intersection));
+    assert!(copy(&a).into_difference(copy(&b)).iter().eq(&difference));
+    assert!(copy(&a)
+        .into_symmetric_difference(copy(&b))
+        .iter()
+        .eq(&symmetric));
+    assert_eq!(copy(&a).into_difference(copy(&a)).len(), 0);
+    assert_eq!(copy(&a).into_intersection(RSet::new()).len(), 0);
+
+    let evens: RSet<u32> = intersection.iter().copied().collect();
+    assert!(evens.is_subset(&a) && evens.is_subset(&b));
+    assert!(a.is_superset(&evens));
+    assert!(!a.is_subset(&b));
+    assert!(!a.is_disjoint(&b));
+    let odds: RSet<u32> = (0..300).filter(|i| i % 2 == 1).collect();
+    assert!(odds.is_disjoint(&a));
+}
+
+#[test]
+fn test_split_and_append() {
+    let mut set: RSet<_> = (0..100).collect();
+    let mut high = set.split_off(&60);
+    assert!(set.iter().copied().eq(0..60));
+    assert!(high.iter().copied().eq(60..100));
+    set.append(&mut high);
+    assert!(high.is_empty());
+    assert_eq!(set.len(), 100);
+}
+
+#[test]
+fn test_std_traits() {
+    let set: RSet<_> = [3, 1, 2].into_iter().collect();
+    assert_eq!(format!("{set:?}"), "{1, 2, 3}");
+    let mut copy = set.clone();
+    assert_eq!(copy, set);
+    copy.extend(&[0]);
+    assert!(copy < set);
+    assert_eq!(RSet::<u8>::default(), RSet::new());
+}
+
+#[test]
+fn test_choose() {
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(47);
+    let mut set = RSet::new();
+    assert_eq!(set.choose(&mut rng), None);
+    set.extend([3, 1, 4]);
+    let mut seen = [false; 5];
+    for _ in 0..100 {
+        seen[*set.choose(&mut rng).unwrap()] = true;
+    }
+    assert_eq!(seen, [false, true, false, true, true]);
+}
@@ -1,4 +1,8 @@
-use infra_bits::rand::RTreeMap;
+use infra_bits::{
+    compare::{Natural, Reverse, TotalOrder},
+    rand::{rtree::Entry, RTreeMap},
+};
+use std::ops::Bound;
 
 #[derive(Debug)]
 struct Dropcheck {
@@ -18,14 +22,691 @@ fn test_should_dropp_all_elements() {
     {
         let mut tree: RTreeMap<char, Dropcheck> = RTreeMap::new();
         for c in 'a'..='e' {
-            tree.insert(c, Dropcheck { inner: c })
+            tree.insert(c, Dropcheck { inner: c });
         }
-        for c in ('a'..='e').into_iter().rev() {
+        for c in ('a'..='e').rev() {
             println!("before delete: {:?}", tree);
-            tree.remove(c);
+            let deleted = tree.remove(&c);
             println!("after delete: {:?}", tree);
-            // assert!(deleted.unwrap().inner == c)
+            assert!(deleted.unwrap().inner == c);
+            assert_eq!(tree.validate(), Ok(()));
         }
     }
     assert_eq!(unsafe { DROPPED }, 5);
 }
+
+#[test]
+fn test_iter_in_order() {
+    let mut tree = RTreeMap::new();
+    for i in [5, 3, 8, 1, 4, 7, 9, 2, 6, 0] {
+        tree.insert(i, i * 10);
+    }
+    assert_eq!(tree.len(), 10);
+    let entries: Vec<_> = tree.iter().map(|(k, v)| (*k, *v)).collect();
+    assert_eq!(entries, (0..10).map(|i| (i, i * 10)).collect::<Vec<_>>());
+    let keys: Vec<_> = tree.keys().rev().copied().collect();
+    assert_eq!(keys, (0..10).rev().collect::<Vec<_>>());
+    assert_eq!(tree.values().len(), 10);
+
+    let mut iter = tree.iter();
+    assert_eq!(iter.next(), Some((&0, &0)));
+    assert_eq!(iter.next_back(), Some((&9, &90)));
+    assert_eq!(iter.len(), 8);
+    assert_eq!(
+        iter.map(|(k, _)| *k).collect::<Vec<_>>(),
+        (1..9).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_iter_mut() {
+    let mut tree = RTreeMap::new();
+    for i in 0..100 {
+        tree.insert(i, i);
+    }
+    for v in tree.values_mut() {
+        *v *= 2;
+    }
+    for (k, v) in &mut tree {
+        *v += k;
+    }
+    assert!(tree.iter().all(|(k, v)| *v == 3 * k));
+    tree.insert(50, 0);
+    assert_eq!(tree.len(), 100);
+    tree.remove(&50);
+    tree.remove(&50);
+    assert_eq!(tree.len(), 99);
+}
+
+#[test]
+fn test_into_iter() {
+    let mut tree = RTreeMap::new();
+    for i in (0..50).rev() {
+        tree.insert(i, i.to_string());
+    }
+    let mut iter = tree.into_iter();
+    assert_eq!(iter.next(), Some((0, "0".to_string())));
+    assert_eq!(iter.next_back(), Some((49, "49".to_string())));
+    assert_eq!(iter.len(), 48);
+    let rest: Vec<_> = iter.by_ref().take(10).map(|(k, _)| k).collect();
+    assert_eq!(rest, (1..11).collect::<Vec<_>>());
+    let back: Vec<_> = iter.rev().map(|(k, _)| k).collect();
+    assert_eq!(back, (11..49).rev().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_range() {
+    let mut tree = RTreeMap::new();
+    for i in (0..20).map(|i| i * 2) {
+        tree.insert(i, i);
+    }
+    let keys = |r: Vec<(&i32, &i32)>| r.into_iter().map(|(k, _)| *k).collect::<Vec<_>>();
+    assert_eq!(keys(tree.range(3..9).collect()), vec![4, 6, 8]);
+    assert_eq!(keys(tree.range(4..=10).collect()), vec![4, 6, 8, 10]);
+    assert_eq!(keys(tree.range(..3).collect()), vec![0, 2]);
+    assert_eq!(keys(tree.range(35..).collect()), vec![36, 38]);
+    assert_eq!(keys(tree.range(5..6).collect()), Vec::<i32>::new());
+    assert_eq!(keys(tree.range(100..).collect()), Vec::<i32>::new());
+    assert_eq!(keys(tree.range(..).rev().collect()).len(), 20);
+    assert_eq!(
+        keys(tree.range(10..=20).rev().collect()),
+        vec![20, 18, 16, 14, 12, 10]
+    );
+
+    let mut range = tree.range(0..10);
+    assert_eq!(range.next(), Some((&0, &0)));
+    assert_eq!(range.next_back(), Some((&8, &8)));
+    assert_eq!(range.next_back(), Some((&6, &6)));
+    assert_eq!(range.next(), Some((&2, &2)));
+    assert_eq!(range.next(), Some((&4, &4)));
+    assert_eq!(range.next(), None);
+    assert_eq!(range.next_back(), None);
+
+    for (_, v) in tree.range_mut(10..20) {
+        *v = -1;
+    }
+    assert_eq!(tree.values().filter(|v| **v == -1).count(), 5);
+}
+
+#[test]
+#[should_panic]
+fn test_range_inverted() {
+    let mut tree = RTreeMap::new();
+    tree.insert(1, ());
+    tree.range((Bound::Included(5), Bound::Excluded(1)));
+}
+
+#[test]
+fn test_order_statistics() {
+    let mut tree = RTreeMap::new();
+    let mut expected = Vec::new();
+    for i in 0..200 {
+        let key = (i * 37) % 101;
+        tree.insert(key, i);
+        if !expected.contains(&key) {
+            expected.push(key);
+        }
+    }
+    for key in (0..101).step_by(3) {
+        tree.remove(&key);
+        expected.retain(|k| *k != key);
+    }
+    expected.sort();
+    for (i, key) in expected.iter().enumerate() {
+        assert_eq!(tree.nth(i).map(|(k, _)| k), Some(key));
+        assert_eq!(tree.rank(key), i);
+    }
+    assert_eq!(tree.nth(expected.len()), None);
+    assert_eq!(tree.rank(&1000), expected.len());
+    assert_eq!(tree.rank(&-1), 0);
+}
+
+fn collect<V: Copy>(tree: &RTreeMap<i32, V>) -> Vec<(i32, V)> {
+    tree.iter().map(|(k, v)| (*k, *v)).collect()
+}
+
+#[test]
+fn test_split_off_and_append() {
+    let mut tree = RTreeMap::new();
+    for i in 0..100 {
+        tree.insert(i, i);
+    }
+    let mut upper = tree.split_off(&60);
+    assert_eq!(tree.len(), 60);
+    assert_eq!(upper.len(), 40);
+    assert_eq!(collect(&tree), (0..60).map(|i| (i, i)).collect::<Vec<_>>());
+    assert_eq!(
+        collect(&upper),
+        (60..100).map(|i| (i, i)).collect::<Vec<_>>()
+    );
+    assert_eq!(upper.nth(0), Some((&60, &60)));
+
+    upper.append(&mut tree);
+    assert!(tree.is_empty());
+    assert_eq!(
+        collect(&upper),
+        (0..100).map(|i| (i, i)).collect::<Vec<_>>()
+    );
+
+    let empty = upper.split_off(&1000);
+    assert!(empty.is_empty());
+    assert_eq!(upper.len(), 100);
+}
+
+#[test]
+fn test_join() {
+    let (mut left, mut right) = (RTreeMap::new(), RTreeMap::new());
+    for i in 0..50 {
+        left.insert(i, 'l');
+        right.insert(i + 50, 'r');
+    }
+    let joined = RTreeMap::join(left, right);
+    assert_eq!(joined.len(), 100);
+    assert_eq!(
+        joined.keys().copied().collect::<Vec<_>>(),
+        (0..100).collect::<Vec<_>>()
+    );
+    assert_eq!(joined.rank(&50), 50);
+}
+
+#[test]
+#[should_panic]
+fn test_join_overlapping() {
+    let (mut left, mut right) = (RTreeMap::new(), RTreeMap::new());
+    left.insert(5, ());
+    right.insert(5, ());
+    RTreeMap::join(left, right);
+}
+
+#[test]
+fn test_union() {
+    let (mut a, mut b) = (RTreeMap::new(), RTreeMap::new());
+    for i in (0..100).step_by(2) {
+        a.insert(i, 'a');
+    }
+    for i in (0..100).step_by(3) {
+        b.insert(i, 'b');
+    }
+    let mut c = RTreeMap::new();
+    c.insert(7, 'c');
+    a.append(&mut c);
+    let union = a.union(b);
+    let expected: Vec<_> = (0..100)
+        .filter(|i| i % 2 == 0 || i % 3 == 0 || *i == 7)
+        .map(|i| {
+            (
+                i,
+                if i % 3 == 0 {
+                    'b'
+                } else if i == 7 {
+                    'c'
+                } else {
+                    'a'
+                },
+            )
+        })
+        .collect();
+    assert_eq!(collect(&union), expected);
+    assert_eq!(union.len(), expected.len());
+}
+
+#[test]
+fn test_entry() {
+    let mut counts = RTreeMap::new();
+    for word in "a b a c b a".split(' ') {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    assert_eq!(counts.get("a"), Some(&3));
+    assert_eq!(counts.get("c"), Some(&1));
+    assert_eq!(counts.len(), 3);
+
+    counts.entry("c").and_modify(|v| *v += 10).or_insert(0);
+    counts
+        .entry("d")
+        .and_modify(|v| *v += 10)
+        .or_insert_with(|| 42);
+    assert_eq!(counts.get("c"), Some(&11));
+    assert_eq!(counts.get("d"), Some(&42));
+
+    match counts.entry("b") {
+        Entry::Occupied(o) => assert_eq!(o.remove_entry(), ("b", 2)),
+        Entry::Vacant(_) => panic!("b is present"),
+    }
+    assert!(!counts.contains("b"));
+    assert_eq!(counts.len(), 3);
+    assert_eq!(
+        counts.keys().copied().collect::<Vec<_>>(),
+        vec!["a", "c", "d"]
+    );
+}
+
+#[test]
+fn test_insert_returns_old_value() {
+    let mut tree = RTreeMap::new();
+    assert_eq!(tree.insert(1, "one"), None);
+    assert_eq!(tree.insert(1, "uno"), Some("one"));
+    assert_eq!(tree.get(&1), Some(&"uno"));
+    assert_eq!(tree.len(), 1);
+}
+
+#[test]
+fn test_borrowed_lookups() {
+    let mut tree = RTreeMap::new();
+    for name in ["alice", "bob", "carol"] {
+        tree.insert(name.to_string(), name.len());
+    }
+    assert!(tree.contains("bob"));
+    assert_eq!(tree.get("carol"), Some(&5));
+    *tree.get_mut("alice").unwrap() = 0;
+    assert_eq!(tree.get("alice"), Some(&0));
+    assert_eq!(tree.rank("bz"), 2);
+    assert_eq!(
+        tree.range::<str, _>((Bound::Included("b"), Bound::Excluded("c")))
+            .count(),
+        1
+    );
+    assert_eq!(tree.remove("bob"), Some(3));
+    assert!(!tree.contains("bob"));
+}
+
+#[test]
+fn test_custom_comparators() {
+    let mut floats = RTreeMap::with_comparator(TotalOrder);
+    for f in [2.5, f64::NAN, -1.0, 0.0] {
+        floats.insert(f, ());
+    }
+    let keys: Vec<_> = floats.keys().copied().collect();
+    assert_eq!(keys[..3], [-1.0, 0.0, 2.5]);
+    assert!(keys[3].is_nan());
+    assert!(floats.contains(&f64::NAN));
+
+    let mut reversed = RTreeMap::with_comparator(Reverse(Natural));
+    for i in 0..5 {
+        reversed.insert(i, i);
+    }
+    assert_eq!(
+        reversed.keys().copied().collect::<Vec<_>>(),
+        vec![4, 3, 2, 1, 0]
+    );
+    assert_eq!(
+        reversed
+            .range((Bound::Included(3), Bound::Included(1)))
+            .count(),
+        3
+    );
+
+    let mut names =
+        RTreeMap::with_comparator(|a: &String, b: &String| a.to_lowercase().cmp(&b.to_lowercase()));
+    names.insert("Bob".to_string(), 1);
+    names.insert("alice".to_string(), 2);
+    assert_eq!(names.insert("BOB".to_string(), 3), Some(1));
+    assert_eq!(names.len(), 2);
+    assert_eq!(names.get(&"bob".to_string()), Some(&3));
+}
+
+#[test]
+fn test_large_map() {
+    let mut tree = RTreeMap::new();
+    for i in 0..200_000 {
+        tree.insert(i, ());
+    }
+    let upper = tree.split_off(&100_000);
+    assert_eq!(upper.len(), 100_000);
+    let joined = RTreeMap::join(tree, upper);
+    assert_eq!(joined.len(), 200_000);
+    assert_eq!(joined.nth(150_000), Some((&150_000, &())));
+}
+
+#[test]
+fn test_from_sorted_iter() {
+    let mut tree = RTreeMap::from_sorted_iter((0..100_000).map(|i| (i, i * 2)));
+    assert_eq!(tree.len(), 100_000);
+    assert!(tree
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq((0..100_000).map(|i| (i, i * 2))));
+    assert_eq!(tree.nth(500), Some((&500, &1000)));
+    assert_eq!(tree.rank(&70_000), 70_000);
+    tree.insert(-1, 0);
+    assert_eq!(tree.remove(&50_000), Some(100_000));
+    assert_eq!(tree.len(), 100_000);
+
+    let dups = RTreeMap::from_sorted_iter([(1, 'a'), (1, 'b'), (2, 'c')]);
+    assert_eq!(collect(&dups), vec![(1, 'b'), (2, 'c')]);
+
+    let unsorted = RTreeMap::from_sorted_iter([(1, ()), (5, ()), (3, ()), (4, ()), (0, ())]);
+    assert_eq!(
+        unsorted.keys().copied().collect::<Vec<_>>(),
+        vec![0, 1, 3, 4, 5]
+    );
+    assert_eq!(unsorted.rank(&4), 3);
+}
+
+#[test]
+fn test_collect_and_extend() {
+    let mut tree: RTreeMap<_, _> = (0..10).map(|i| (i, i)).collect();
+    tree.extend((10..20).map(|i| (i, i)));
+    tree.extend([(5, 50), (25, 25)]);
+    assert_eq!(tree.len(), 21);
+    assert_eq!(tree.get(&5), Some(&50));
+    assert_eq!(tree.nth(20), Some((&25, &25)));
+}
+
+#[test]
+fn test_validate_after_every_operation() {
+    let mut tree = RTreeMap::new();
+    for i in 0..500u32 {
+        let key = i.wrapping_mul(2_654_435_761) % 199;
+        if i % 4 == 0 {
+            tree.remove(&key);
+        } else {
+            tree.insert(key, i);
+        }
+        assert_eq!(tree.validate(), Ok(()));
+    }
+    let mut upper = tree.split_off(&100);
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!(upper.validate(), Ok(()));
+    upper.append(&mut tree);
+    assert_eq!(upper.validate(), Ok(()));
+    let other = RTreeMap::from_sorted_iter((50..300).map(|k| (k, 0)));
+    assert_eq!(other.validate(), Ok(()));
+    assert_eq!(upper.union(other).validate(), Ok(()));
+}
+
+#[test]
+fn test_neighbour_queries() {
+    let mut tree: RTreeMap<_, _> = [10, 20, 30, 40].into_iter().map(|k| (k, k / 10)).collect();
+    assert_eq!(tree.floor(&25), Some((&20, &2)));
+    assert_eq!(tree.floor(&20), Some((&20, &2)));
+    assert_eq!(tree.floor(&5), None);
+    assert_eq!(tree.ceiling(&25), Some((&30, &3)));
+    assert_eq!(tree.ceiling(&30), Some((&30, &3)));
+    assert_eq!(tree.ceiling(&45), None);
+    assert_eq!(tree.predecessor(&30), Some((&20, &2)));
+    assert_eq!(tree.predecessor(&10), None);
+    assert_eq!(tree.successor(&30), Some((&40, &4)));
+    assert_eq!(tree.successor(&40), None);
+
+    assert_eq!(tree.first_key_value(), Some((&10, &1)));
+    assert_eq!(tree.last_key_value(), Some((&40, &4)));
+    assert_eq!(tree.pop_first(), Some((10, 1)));
+    assert_eq!(tree.pop_last(), Some((40, 4)));
+    assert_eq!(tree.len(), 2);
+    assert_eq!(tree.validate(), Ok(()));
+    tree.pop_first();
+    tree.pop_first();
+    assert_eq!(tree.pop_first(), None);
+    assert_eq!(tree.last_key_value(), None);
+}
+
+#[test]
+fn test_cursor() {
+    let tree: RTreeMap<_, _> = (0..10).map(|k| (k * 2, k)).collect();
+    let mut cursor = tree.lower_bound(Bound::Included(&7));
+    assert_eq!(cursor.peek(), Some((&8, &4)));
+    assert_eq!(cursor.peek_prev(), Some((&6, &3)));
+    cursor.move_prev();
+    cursor.move_prev();
+    assert_eq!(cursor.peek(), Some((&4, &2)));
+
+    let mut cursor = tree.lower_bound(Bound::Excluded(&18));
+    assert_eq!(cursor.peek(), None);
+    cursor.move_next();
+    assert_eq!(cursor.peek(), Some((&0, &0)));
+    cursor.move_prev();
+    cursor.move_prev();
+    assert_eq!(cursor.peek(), Some((&18, &9)));
+
+    let mut keys = vec![];
+    let mut cursor = tree.lower_bound(Bound::<&i32>::Unbounded);
+    while let Some((k, _)) = cursor.peek() {
+        keys.push(*k);
+        cursor.move_next();
+    }
+    assert_eq!(keys, (0..10).map(|k| k * 2).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_cursor_mut() {
+    let mut tree: RTreeMap<_, _> = (0..100).map(|k| (k * 2, k)).collect();
+    let mut cursor = tree.lower_bound_mut(Bound::Included(&0));
+    while cursor.peek().is_some() {
+        let (&k, _) = cursor.peek().unwrap();
+        if k % 4 == 0 {
+            assert_eq!(cursor.remove_current(), Some((k, k / 2)));
+        } else {
+            cursor.insert_before(k - 1, 0);
+            *cursor.peek_mut().unwrap().1 += 1000;
+            cursor.move_next();
+        }
+    }
+    cursor.insert_before(1000, 0);
+    assert_eq!(cursor.remove_current(), None);
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!(tree.len(), 101);
+    assert_eq!(tree.get(&5), Some(&0));
+    assert_eq!(tree.get(&6), Some(&1003));
+    assert_eq!(tree.get(&8), None);
+    assert_eq!(tree.last_key_value(), Some((&1000, &0)));
+}
+
+#[test]
+#[should_panic]
+fn test_cursor_insert_out_of_order() {
+    let mut tree: RTreeMap<_, _> = [(1, ()), (5, ())].into_iter().collect();
+    tree.lower_bound_mut(Bound::Included(&5))
+        .insert_before(0, ());
+}
+
+#[test]
+fn test_intersection_and_differences() {
+    let build = |step: i32, tag: char| -> RTreeMap<i32, char> {
+        (0..500).step_by(step as usize).map(|k| (k, tag)).collect()
+    };
+    let both = build(2, 'a').intersection(build(3, 'b'));
+    assert_eq!(both.validate(), Ok(()));
+    assert!(both.keys().copied().eq((0..500).step_by(6)));
+    assert!(both.values().all(|v| *v == 'a'));
+    let both = build(3, 'b').intersection(build(2, 'a'));
+    assert!(both.values().all(|v| *v == 'b'));
+    assert_eq!(both.len(), 84);
+
+    let only = build(2, 'a').difference(build(3, 'b'));
+    assert_eq!(only.validate(), Ok(()));
+    assert!(only
+        .keys()
+        .copied()
+        .eq((0..500).filter(|k| k % 2 == 0 && k % 3 != 0)));
+
+    let either = build(2, 'a').symmetric_difference(build(3, 'b'));
+    assert_eq!(either.validate(), Ok(()));
+    assert!(either
+        .keys()
+        .copied()
+        .eq((0..500).filter(|k| (k % 2 == 0) != (k % 3 == 0))));
+    assert_eq!(either.get(&3), Some(&'b'));
+    assert_eq!(either.get(&2), Some(&'a'));
+}
+
+#[test]
+fn test_set_operations_match_btree_map() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::BTreeMap;
+    let mut rng = StdRng::seed_from_u64(45);
+    for round in 0..20 {
+        let mut random_map = |len: usize, tag: u32| -> BTreeMap<u32, u32> {
+            (0..len)
+                .map(|_| (rng.gen_range(0..(len as u32 * 2)), tag))
+                .collect()
+        };
+        let a = random_map(2000 + round * 100, 1);
+        let b = random_map(3000 - round * 100, 2);
+        let build = |m: &BTreeMap<u32, u32>| -> RTreeMap<u32, u32> {
+            m.iter().map(|(k, v)| (*k, *v)).collect()
+        };
+
+        let union = build(&a).union(build(&b));
+        let mut expected = a.clone();
+        expected.extend(b.iter().map(|(k, v)| (*k, *v)));
+        assert_eq!(union.validate(), Ok(()));
+        assert!(union.into_iter().eq(expected));
+
+        let intersection = build(&a).intersection(build(&b));
+        assert_eq!(intersection.validate(), Ok(()));
+        assert!(intersection.into_iter().eq(a
+            .iter()
+            .filter(|(k, _)| b.contains_key(k))
+            .map(|(k, v)| (*k, *v))));
+
+        let difference = build(&a).difference(build(&b));
+        assert_eq!(difference.validate(), Ok(()));
+        assert!(difference.into_iter().eq(a
+            .iter()
+            .filter(|(k, _)| !b.contains_key(k))
+            .map(|(k, v)| (*k, *v))));
+
+        let symmetric = build(&a).symmetric_difference(build(&b));
+        let mut expected: BTreeMap<_, _> = b
+            .iter()
+            .filter(|(k, _)| !a.contains_key(k))
+            .map(|(k, v)| (*k, *v))
+            .collect();
+        expected.extend(
+            a.iter()
+                .filter(|(k, _)| !b.contains_key(k))
+                .map(|(k, v)| (*k, *v)),
+        );
+        assert_eq!(symmetric.validate(), Ok(()));
+        assert!(symmetric.into_iter().eq(expected));
+    }
+}
+
+#[test]
+fn test_choose_uniform() {
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut tree: RTreeMap<u32, ()> = RTreeMap::new();
+    assert_eq!(tree.choose(&mut rng), None);
+    tree.extend((0..10).map(|k| (k, ())));
+    let mut counts = [0; 10];
+    for _ in 0..100_000 {
+        counts[*tree.choose(&mut rng).unwrap().0 as usize] += 1;
+    }
+    assert!(
+        counts.iter().all(|c| (9_000..11_000).contains(c)),
+        "{counts:?}"
+    );
+}
+
+#[test]
+fn test_std_traits() {
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+    };
+    let hash = |tree: &RTreeMap<i32, char>| {
+        let mut hasher = DefaultHasher::new();
+        tree.hash(&mut hasher);
+        hasher.finish()
+    };
+    let tree: RTreeMap<_, _> = [(2, 'b'), (1, 'a')].into_iter().collect();
+    assert_eq!(format!("{tree:?}"), "{1: 'a', 2: 'b'}");
+    assert_eq!(tree[&2], 'b');
+
+    let mut copy = tree.clone();
+    assert_eq!(copy, tree);
+    assert_eq!(hash(&copy), hash(&tree));
+    copy.extend([(&3, &'c')]);
+    assert_ne!(copy, tree);
+    assert!(tree < copy);
+    assert_eq!(copy.validate(), Ok(()));
+    assert_eq!(RTreeMap::<i32, char>::default(), RTreeMap::new());
+
+    fn send_sync<T: Send + Sync>(_: &T) {}
+    send_sync(&tree);
+    send_sync(&tree.iter());
+    let handle = std::thread::spawn(move || copy.len());
+    assert_eq!(handle.join().unwrap(), 3);
+}
+
+#[test]
+#[should_panic]
+fn test_index_missing_key() {
+    let tree: RTreeMap<i32, u8> = RTreeMap::new();
+    let _ = tree[&1];
+}
+
+#[test]
+fn test_retain_and_extract_if() {
+    let mut tree: RTreeMap<i32, i32> = (0..1000).map(|i| (i, i)).collect();
+    tree.retain(|k, v| {
+        *v *= 2;
+        k % 3 != 0
+    });
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!(tree.len(), 666);
+    assert!(tree.iter().all(|(k, v)| k % 3 != 0 && *v == 2 * k));
+    assert_eq!(tree.nth(1), Some((&2, &4)));
+
+    let odd: Vec<_> = tree.extract_if(|k, _| k % 2 == 1).map(|(k, _)| k).collect();
+    assert_eq!(odd.len(), 333);
+    assert!(odd.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(tree.validate(), Ok(()));
+    assert!(tree.keys().all(|k| k % 2 == 0 && k % 3 != 0));
+
+    // Entries that were not visited stay in the map.
+    let len = tree.len();
+    let mut iter = tree.extract_if(|_, _| true);
+    assert_eq!(iter.next(), Some((2, 4)));
+    assert_eq!(iter.next(), Some((4, 8)));
+    drop(iter);
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!(tree.len(), len - 2);
+    assert_eq!(tree.first_key_value(), Some((&8, &16)));
+}
+
+#[test]
+fn test_extract_if_panic_keeps_entries() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    let mut tree: RTreeMap<i32, ()> = (0..100).map(|i| (i, ())).collect();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        tree.retain(|&k, _| {
+            assert!(k < 50);
+            k % 2 == 0
+        })
+    }));
+    assert!(result.is_err());
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!(tree.len(), 75);
+    assert!(tree.contains(&50));
+    assert!(!tree.contains(&49));
+}
+
+#[test]
+fn test_drain() {
+    let mut tree: RTreeMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    assert!(tree.drain_range(10..20).eq((10..20).map(|i| (i, i))));
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!(tree.len(), 90);
+
+    let mut drain = tree.drain_range((Bound::Excluded(50), Bound::Included(60)));
+    assert_eq!(drain.len(), 10);
+    assert_eq!(drain.next_back(), Some((60, 60)));
+    drop(drain);
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!(tree.range(45..65).count(), 10);
+
+    assert_eq!(tree.drain_range(..5).count(), 5);
+    assert_eq!(tree.drain_range(95..).count(), 5);
+    assert_eq!(tree.drain_range(200..300).count(), 0);
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!(tree.len(), 70);
+
+    let drained: Vec<_> = tree.drain().collect();
+    assert_eq!(drained.len(), 70);
+    assert!(tree.is_empty());
+    assert_eq!(tree.validate(), Ok(()));
+    tree.insert(1, 1);
+    assert_eq!(tree.len(), 1);
+}
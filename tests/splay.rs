@@ -0,0 +1,105 @@
+use infra_bits::{compare::TotalOrder, splay::SplayTree};
+
+#[test]
+fn test_borrowed_lookups() {
+    let mut tree = SplayTree::new();
+    for name in ["alice", "bob", "carol"] {
+        tree.insert(name.to_string(), name.len());
+    }
+    assert!(tree.contains("bob"));
+    assert!(!tree.contains("dave"));
+    assert_eq!(tree.remove("carol"), Some(5));
+    assert!(!tree.contains("carol"));
+}
+
+#[test]
+fn test_custom_comparator() {
+    let mut tree = SplayTree::with_comparator(TotalOrder);
+    for f in [1.0, f64::NAN, -0.5] {
+        tree.insert(f, ());
+    }
+    assert!(tree.contains(&f64::NAN));
+    assert_eq!(tree.remove(&-0.5), Some(()));
+    assert!(!tree.contains(&-0.5));
+}
+
+#[test]
+fn test_remove_root_and_inner_nodes() {
+    let mut tree = SplayTree::new();
+    for i in [5, 2, 8, 1, 3, 7, 9] {
+        tree.insert(i, i);
+    }
+    assert_eq!(tree.validate(), Ok(()));
+    for i in [5, 1, 9, 3] {
+        assert_eq!(tree.remove(&i), Some(i));
+        assert!(!tree.contains(&i));
+        assert_eq!(tree.validate(), Ok(()));
+    }
+    assert_eq!(tree.remove(&5), None);
+    for i in [2, 7, 8] {
+        assert!(tree.contains(&i));
+    }
+}
+
+#[test]
+fn test_drop_degenerate_tree() {
+    let mut tree = SplayTree::new();
+    // Sorted inserts leave the tree as a single path of nodes.
+    for i in 0..2_000_000 {
+        tree.insert(i, ());
+    }
+    assert!(tree.contains(&0));
+    assert!(tree.contains(&1_999_999));
+    drop(tree);
+}
+
+#[test]
+fn test_iteration_and_len() {
+    let mut tree: SplayTree<_, _> = [(3, 'c'), (1, 'a'), (2, 'b')].into_iter().collect();
+    assert_eq!(tree.len(), 3);
+    assert_eq!(tree.insert(2, 'B'), Some('b'));
+    assert_eq!(tree.len(), 3);
+    assert_eq!(format!("{tree:?}"), "{1: 'a', 2: 'B', 3: 'c'}");
+    assert!(tree.iter().rev().map(|(k, _)| *k).eq([3, 2, 1]));
+    for (_, v) in &mut tree {
+        *v = v.to_ascii_uppercase();
+    }
+    assert_eq!(tree[&1], 'A');
+    assert_eq!(tree.get(&4), None);
+    *tree.get_mut(&3).unwrap() = 'z';
+
+    let copy = tree.clone();
+    assert_eq!(copy, tree);
+    assert_eq!(copy.validate(), Ok(()));
+    assert!(tree.into_iter().eq([(1, 'A'), (2, 'B'), (3, 'z')]));
+
+    let mut tree: SplayTree<_, _> = (0..10).map(|i| (i, i)).collect();
+    assert_eq!(tree.pop_first(), Some((0, 0)));
+    assert_eq!(tree.pop_last(), Some((9, 9)));
+    assert_eq!(tree.validate(), Ok(()));
+    let mut iter = tree.into_iter();
+    assert_eq!(iter.next_back(), Some((8, 8)));
+    assert_eq!(iter.len(), 7);
+
+    fn send_sync<T: Send + Sync>() {}
+    send_sync::<SplayTree<String, u32>>();
+}
+
+#[test]
+fn test_get_mut_splays() {
+    let mut tree = SplayTree::new();
+    // Sorted inserts leave the tree as a single path of nodes, with 0 at the bottom.
+    for i in 0..200_000 {
+        tree.insert(i, i);
+    }
+    // Without splaying, every one of these lookups would walk the whole path.
+    for round in 0..10_000 {
+        let key = round % 4;
+        *tree.get_mut(&key).unwrap() += 1;
+        assert_eq!(tree.get_mut(&-1), None);
+    }
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!(tree.get(&0), Some(&2_500));
+    assert_eq!(tree.get(&3), Some(&2_503));
+    assert_eq!(tree.get(&199_999), Some(&199_999));
+}
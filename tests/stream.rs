@@ -0,0 +1,93 @@
+#![cfg(feature = "stream")]
+
+use futures::{executor::block_on, future, StreamExt};
+use infra_bits::rand::{EventEmmiter, Rate, Timer};
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// A clock that never sleeps and only records the delays it was asked for.
+#[derive(Default, Clone)]
+struct MockTimer {
+    slept: Rc<RefCell<Vec<Duration>>>,
+}
+
+impl Timer for MockTimer {
+    type Sleep = future::Ready<()>;
+
+    fn sleep(&mut self, duration: Duration) -> Self::Sleep {
+        self.slept.borrow_mut().push(duration);
+        future::ready(())
+    }
+}
+
+/// A sleep that is pending on its first poll.
+struct Yield(bool);
+
+impl Future for Yield {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+struct YieldTimer;
+
+impl Timer for YieldTimer {
+    type Sleep = Yield;
+
+    fn sleep(&mut self, _: Duration) -> Self::Sleep {
+        Yield(false)
+    }
+}
+
+#[test]
+fn test_constant_rate() {
+    let emitter = EventEmmiter::new(&[0.5, 0.5], ['a', 'b']);
+    let timer = MockTimer::default();
+    let rate = Rate::Constant(Duration::from_millis(10));
+    let events: Vec<&char> = block_on(emitter.stream(rate, timer.clone()).take(100).collect());
+    assert_eq!(events.len(), 100);
+    assert!(events.iter().all(|e| **e == 'a' || **e == 'b'));
+    let slept = timer.slept.borrow();
+    assert_eq!(slept.len(), 100);
+    assert!(slept.iter().all(|d| *d == Duration::from_millis(10)));
+}
+
+#[test]
+fn test_poisson_rate() {
+    let emitter = EventEmmiter::new(&[1.0], [()]);
+    let timer = MockTimer::default();
+    let rate = Rate::Poisson {
+        mean: Duration::from_millis(100),
+    };
+    let n = 100000;
+    block_on(emitter.stream(rate, timer.clone()).take(n).count());
+    let slept = timer.slept.borrow();
+    let mean = slept.iter().sum::<Duration>().as_secs_f64() / n as f64;
+    assert!((mean - 0.1).abs() < 0.005);
+    assert!(slept.iter().any(|d| *d > Duration::from_millis(100)));
+    assert!(slept.iter().any(|d| *d < Duration::from_millis(100)));
+}
+
+#[test]
+fn test_waits_for_timer() {
+    let emitter = EventEmmiter::new(&[1.0], [42]);
+    let mut stream = emitter.stream(Rate::Constant(Duration::ZERO), YieldTimer);
+    let waker = futures::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    assert_eq!(stream.poll_next_unpin(&mut cx), Poll::Pending);
+    assert_eq!(stream.poll_next_unpin(&mut cx), Poll::Ready(Some(&42)));
+    assert_eq!(stream.poll_next_unpin(&mut cx), Poll::Pending);
+}
@@ -0,0 +1,78 @@
+use infra_bits::rand::RTreeMap;
+
+#[test]
+fn test_commit_and_rollback() {
+    let mut map: RTreeMap<_, _> = (0..10).map(|i| (i, i)).collect();
+    let original = map.clone();
+
+    let mut tx = map.begin();
+    assert_eq!(tx.insert(3, 30), Some(3));
+    assert_eq!(tx.insert(20, 20), None);
+    assert_eq!(tx.remove(&5), Some(5));
+    assert_eq!(tx.remove(&50), None);
+    assert_eq!(tx.changes(), 3);
+    assert_eq!(tx.get(&3), Some(&30));
+    assert_eq!(tx.len(), 10);
+    tx.rollback();
+    assert_eq!(map, original);
+    assert_eq!(map.validate(), Ok(()));
+
+    // Dropping an uncommitted transaction rolls it back as well.
+    map.begin().insert(11, 11);
+    assert_eq!(map, original);
+
+    let mut tx = map.begin();
+    tx.remove(&0);
+    tx.insert(0, 100);
+    tx.commit();
+    assert_eq!(map.get(&0), Some(&100));
+    assert_eq!(map.len(), 10);
+}
+
+#[test]
+fn test_savepoints() {
+    let mut map = RTreeMap::new();
+    let mut tx = map.begin();
+    tx.insert("a", 1);
+    let first = tx.savepoint();
+    tx.insert("b", 2);
+    tx.insert("a", 10);
+    let second = tx.savepoint();
+    tx.remove("b");
+    tx.rollback_to(second);
+    assert_eq!(tx.get("b"), Some(&2));
+
+    tx.rollback_to(first);
+    assert!(tx.iter().eq([(&"a", &1)]));
+    // A savepoint stays valid after rolling back to it.
+    tx.insert("c", 3);
+    tx.rollback_to(first);
+    assert!(!tx.contains("c"));
+    tx.commit();
+    assert!(map.iter().eq([(&"a", &1)]));
+}
+
+#[test]
+#[should_panic = "savepoint was released"]
+fn test_released_savepoint() {
+    let mut map = RTreeMap::new();
+    let mut tx = map.begin();
+    let first = tx.savepoint();
+    let second = tx.savepoint();
+    tx.rollback_to(first);
+    tx.insert(1, 1);
+    tx.savepoint();
+    tx.rollback_to(second);
+}
+
+#[test]
+#[should_panic = "savepoint belongs to another transaction"]
+fn test_foreign_savepoint() {
+    let mut first: RTreeMap<i32, i32> = RTreeMap::new();
+    let mut second = RTreeMap::new();
+    let sp = first.begin().savepoint();
+    let mut tx = second.begin();
+    tx.savepoint();
+    tx.insert(1, 1);
+    tx.rollback_to(sp);
+}
@@ -0,0 +1,76 @@
+use infra_bits::rand::TreapVec;
+
+#[test]
+fn test_matches_vec() {
+    let mut vec = TreapVec::new();
+    let mut expected = Vec::new();
+    for i in 0..2000usize {
+        let index = (i * 7919) % (expected.len() + 1);
+        if i % 3 == 0 && !expected.is_empty() {
+            let index = index.min(expected.len() - 1);
+            assert_eq!(vec.remove(index), expected.remove(index));
+        } else {
+            vec.insert(index, i);
+            expected.insert(index, i);
+        }
+        if i % 7 == 0 {
+            let b = index.min(expected.len());
+            let a = b / 2;
+            vec.reverse(a..b);
+            expected[a..b].reverse();
+        }
+    }
+    assert_eq!(vec.len(), expected.len());
+    assert!(vec.iter().eq(expected.iter()));
+    for (i, value) in expected.iter().enumerate() {
+        assert_eq!(vec[i], *value);
+    }
+    assert_eq!(vec.get(expected.len()), None);
+}
+
+#[test]
+fn test_split_and_append() {
+    let vec: TreapVec<_> = (0..100).collect();
+    let (mut left, mut right) = vec.split_at(30);
+    assert!(left.iter().copied().eq(0..30));
+    assert!(right.iter().copied().eq(30..100));
+
+    right.reverse(..);
+    right[0] += 1000;
+    left.append(&mut right);
+    assert!(right.is_empty());
+    let expected: Vec<_> = (0..30).chain([1099]).chain((30..99).rev()).collect();
+    assert!(left.iter().eq(expected.iter()));
+    assert_eq!(left.pop(), Some(30));
+    assert_eq!(format!("{:?}", left.split_at(2).0), "[0, 1]");
+}
+
+#[test]
+fn test_nested_reversals() {
+    let mut vec: TreapVec<_> = (0..10).collect();
+    vec.reverse(2..8);
+    vec.reverse(0..5);
+    vec.reverse(4..=9);
+    let mut expected: Vec<_> = (0..10).collect();
+    expected[2..8].reverse();
+    expected[0..5].reverse();
+    expected[4..=9].reverse();
+    assert!(vec.iter().eq(expected.iter()));
+    *vec.get_mut(3).unwrap() = 100;
+    expected[3] = 100;
+    assert!(vec.iter().eq(expected.iter()));
+}
+
+#[test]
+#[should_panic = "range out of bounds"]
+fn test_reverse_overflowing_bound() {
+    let mut vec: TreapVec<_> = (0..10).collect();
+    vec.reverse(3..=usize::MAX);
+}
+
+#[test]
+fn test_send_to_thread() {
+    let vec: TreapVec<_> = (0..10).collect();
+    let handle = std::thread::spawn(move || vec.iter().sum::<i32>());
+    assert_eq!(handle.join().unwrap(), 45);
+}